@@ -1,5 +1,11 @@
 use crate::types::*;
-use crate::{consts::*, score::ScoreResource};
+use crate::{
+    calibration::Calibration,
+    clock::{SongClock, SongClockLabel},
+    consts::*,
+    lane_input::LaneEvent,
+    score::{Judgment, ScoreResource},
+};
 use bevy::{app::Events, prelude::*};
 
 pub struct ArrowsPlugin;
@@ -12,9 +18,14 @@ impl Plugin for ArrowsPlugin {
             )
             .add_system_set(
                 SystemSet::on_update(AppState::Game)
-                    .with_system(spawn_arrows.system())
+                    .with_system(spawn_arrows.system().after(SongClockLabel))
                     .with_system(move_arrows.system())
-                    .with_system(despawn_arrows.system()),
+                    .with_system(despawn_arrows.system().after(SongClockLabel))
+                    .with_system(hold_arrows.system().after(SongClockLabel))
+                    .with_system(update_hold_tails.system().after(SongClockLabel)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Game).with_system(teardown_arrows.system()),
             );
     }
 }
@@ -24,6 +35,9 @@ pub struct ArrowMaterialResource {
     blue_texture: Handle<ColorMaterial>,
     green_texture: Handle<ColorMaterial>,
     border_texture: Handle<ColorMaterial>,
+    red_tail: Handle<ColorMaterial>,
+    blue_tail: Handle<ColorMaterial>,
+    green_tail: Handle<ColorMaterial>,
 }
 
 impl FromWorld for ArrowMaterialResource {
@@ -41,51 +55,129 @@ impl FromWorld for ArrowMaterialResource {
             blue_texture: materials.add(blue_handle.into()),
             green_texture: materials.add(green_handle.into()),
             border_texture: materials.add(border_handle.into()),
+            red_tail: materials.add(Color::rgba(0.9, 0.3, 0.3, 0.7).into()),
+            blue_tail: materials.add(Color::rgba(0.3, 0.5, 0.9, 0.7).into()),
+            green_tail: materials.add(Color::rgba(0.3, 0.8, 0.3, 0.7).into()),
         }
     }
 }
 
-struct Arrow {
+impl ArrowMaterialResource {
+    /// Material for an arrow moving at `speed`
+    pub fn speed_material(&self, speed: Speed) -> Handle<ColorMaterial> {
+        match speed {
+            Speed::Slow => self.red_texture.clone(),
+            Speed::Medium => self.blue_texture.clone(),
+            Speed::Fast => self.green_texture.clone(),
+        }
+    }
+
+    /// Material for the tail of a hold arrow moving at `speed`
+    pub fn tail_material(&self, speed: Speed) -> Handle<ColorMaterial> {
+        match speed {
+            Speed::Slow => self.red_tail.clone(),
+            Speed::Medium => self.blue_tail.clone(),
+            Speed::Fast => self.green_tail.clone(),
+        }
+    }
+
+    /// Material for the border of the target arrows
+    pub fn border_material(&self) -> Handle<ColorMaterial> {
+        self.border_texture.clone()
+    }
+}
+
+pub struct Arrow {
     speed: Speed,
     direction: Directions,
+    click_time: f64,
+    duration: Option<f64>,
+}
+
+impl Arrow {
+    /// Moves the arrow `secs` later in the song, and back on screen to match
+    pub fn delay(&mut self, secs: f64, transform: &mut Transform) {
+        self.click_time += secs;
+        transform.translation.x -= secs as f32 * self.speed.value();
+    }
+
+    /// Song time at which a hold arrow can be released
+    fn end_time(&self) -> f64 {
+        self.click_time + self.duration.unwrap_or(0.)
+    }
+}
+
+/// Arrow that wasn't clicked in time, it falls off the screen before being despawned
+struct Missed;
+
+/// Hold arrow that has been clicked, it stays on the target until it's released.
+/// Keeps the judgment of the click
+struct Holding(Judgment);
+
+/// Tail of a hold arrow, child of its [Arrow]
+struct HoldTail;
+
+/// Transform of a hold tail `length` pixels long, behind an arrow facing `direction`
+fn tail_transform(direction: Directions, length: f32) -> Transform {
+    // Undo the rotation of the arrow, so the tail always trails horizontally
+    let rotation = Quat::from_rotation_z(-direction.rotation());
+    let mut transform = Transform::from_translation(rotation * Vec3::new(-length / 2., 0., -0.5));
+    transform.rotation = rotation;
+    transform
 }
 
 fn spawn_arrows(
     mut commands: Commands,
     mut song_config: ResMut<SongConfig>,
     materials: Res<ArrowMaterialResource>,
-    time: Res<Time>,
+    clock: Res<SongClock>,
+    calibration: Res<Calibration>,
 ) {
-    let secs = time.seconds_since_startup() - 3.;
-    let secs_last = secs - time.delta_seconds_f64();
+    // Arrows are drawn early by the visual offset, so they're seen on time
+    let secs = clock.seconds() + calibration.visual_secs();
 
+    // Arrows are sorted by spawn time, so every arrow due before `secs` is at the front
     let mut remove_counter = 0;
     for arrow in &song_config.arrows {
-        if secs_last < arrow.spawn_time && arrow.spawn_time < secs {
+        if arrow.spawn_time < secs {
             remove_counter += 1;
 
-            let material = match arrow.speed {
-                Speed::Slow => materials.red_texture.clone(),
-                Speed::Medium => materials.blue_texture.clone(),
-                Speed::Fast => materials.green_texture.clone(),
-            };
+            let material = materials.speed_material(arrow.speed);
 
+            // Arrows spawned late, like after a long frame, start as far along as they'd have moved
+            let position = SPAWN_POSITION + (secs - arrow.spawn_time) as f32 * arrow.speed.value();
             let mut transform =
-                Transform::from_translation(Vec3::new(SPAWN_POSITION, arrow.direction.y(), 1.));
+                Transform::from_translation(Vec3::new(position, arrow.direction.y(), 1.));
 
             transform.rotate(Quat::from_rotation_z(arrow.direction.rotation()));
 
-            commands
-                .spawn_bundle(SpriteBundle {
-                    material,
-                    sprite: Sprite::new(Vec2::new(140., 140.)),
-                    transform,
-                    ..Default::default()
-                })
-                .insert(Arrow {
-                    speed: arrow.speed,
-                    direction: arrow.direction,
+            let mut entity = commands.spawn_bundle(SpriteBundle {
+                material,
+                sprite: Sprite::new(Vec2::new(140., 140.)),
+                transform,
+                ..Default::default()
+            });
+            entity.insert(Arrow {
+                speed: arrow.speed,
+                direction: arrow.direction,
+                click_time: arrow.click_time,
+                duration: arrow.duration,
+            });
+
+            if let Some(duration) = arrow.duration {
+                let length = duration as f32 * arrow.speed.value();
+                let tail_material = materials.tail_material(arrow.speed);
+                entity.with_children(|parent| {
+                    parent
+                        .spawn_bundle(SpriteBundle {
+                            material: tail_material,
+                            sprite: Sprite::new(Vec2::new(length, HOLD_TAIL_WIDTH)),
+                            transform: tail_transform(arrow.direction, length),
+                            ..Default::default()
+                        })
+                        .insert(HoldTail);
                 });
+            }
         } else {
             break;
         }
@@ -96,7 +188,7 @@ fn spawn_arrows(
     }
 }
 
-fn move_arrows(time: Res<Time>, mut query: Query<(&mut Transform, &Arrow)>) {
+fn move_arrows(time: Res<Time>, mut query: Query<(&mut Transform, &Arrow), Without<Holding>>) {
     for (mut transform, arrow) in query.iter_mut() {
         transform.translation.x += time.delta_seconds() * arrow.speed.value();
 
@@ -116,40 +208,164 @@ fn move_arrows(time: Res<Time>, mut query: Query<(&mut Transform, &Arrow)>) {
 
 pub struct CorrectArrowEvent {
     pub direction: Directions,
+    pub judgment: Judgment,
     pub points: usize,
 }
 
 fn despawn_arrows(
     mut commands: Commands,
-    query: Query<(Entity, &Transform, &Arrow)>,
-    keyboard_input: Res<Input<KeyCode>>,
+    query: Query<(Entity, &Transform, &Arrow, Option<&Missed>), Without<Holding>>,
+    mut lane_events: EventReader<LaneEvent>,
+    clock: Res<SongClock>,
     mut score: ResMut<ScoreResource>,
     mut correct_arrow_events: ResMut<Events<CorrectArrowEvent>>,
 ) {
-    for (entity, transform, arrow) in query.iter() {
-        let pos = transform.translation.x;
+    let secs = clock.seconds();
+
+    // Arrows clicked by the presses of this frame, which are still in the query
+    let mut clicked = Vec::new();
+    for event in lane_events.iter().filter(|event| event.pressed) {
+        // Presses are judged at the song time of their input rather than at the frame that sees them.
+        // Only gamepads say when that was, keys have the time of the frame that read them
+        let press_secs = clock.seconds_at(event.time);
+
+        // A press only clicks the arrow closest to it, if it's inside the judgment windows
+        let closest = query
+            .iter()
+            .filter(|(entity, _, arrow, missed)| {
+                arrow.direction == event.direction && missed.is_none() && !clicked.contains(entity)
+            })
+            .map(|(entity, _, arrow, _)| (entity, arrow, (press_secs - arrow.click_time) * 1000.))
+            .filter(|(_, _, offset)| offset.abs() <= BAD_WINDOW)
+            .min_by(|(_, _, a), (_, _, b)| a.abs().partial_cmp(&b.abs()).unwrap());
 
-        if (TARGET_POSITION - THRESHOLD..=TARGET_POSITION + THRESHOLD).contains(&pos)
-            && arrow.direction.key_just_pressed(&keyboard_input)
-        {
-            commands.entity(entity).despawn();
-            let points = score.increase_correct(TARGET_POSITION - pos);
+        if let Some((entity, arrow, offset)) = closest {
+            let judgment = Judgment::from_offset(offset);
+
+            // Hold arrows stay until they're released
+            if arrow.duration.is_some() {
+                commands.entity(entity).insert(Holding(judgment));
+            } else {
+                commands.entity(entity).despawn_recursive();
+            }
+            clicked.push(entity);
+            let points = score.increase_correct(judgment);
 
             correct_arrow_events.send(CorrectArrowEvent {
                 direction: arrow.direction,
+                judgment,
                 points,
             })
         }
+    }
+
+    for (entity, transform, arrow, missed) in query.iter() {
+        if clicked.contains(&entity) {
+            continue;
+        }
 
-        if pos >= 2. * TARGET_POSITION {
-            commands.entity(entity).despawn();
+        if missed.is_none() && (secs - arrow.click_time) * 1000. > BAD_WINDOW {
+            commands.entity(entity).insert(Missed);
             score.increase_fails();
         }
+
+        if transform.translation.x >= 2. * TARGET_POSITION {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn hold_arrows(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &Arrow, &Holding)>,
+    mut lane_events: EventReader<LaneEvent>,
+    lanes: Res<Input<Directions>>,
+    clock: Res<SongClock>,
+    mut score: ResMut<ScoreResource>,
+    mut correct_arrow_events: ResMut<Events<CorrectArrowEvent>>,
+) {
+    let secs = clock.seconds();
+    let releases: Vec<_> = lane_events
+        .iter()
+        .filter(|event| !event.pressed)
+        .map(|event| (event.direction, clock.seconds_at(event.time)))
+        .collect();
+
+    for (entity, mut transform, arrow, holding) in query.iter_mut() {
+        transform.translation.x = TARGET_POSITION;
+
+        // A hold released in a frame that had already seen it clicked has no release of its own,
+        // so it counts as released now
+        let released = releases
+            .iter()
+            .find(|(direction, _)| *direction == arrow.direction)
+            .map(|(_, release_secs)| *release_secs)
+            .or_else(|| Some(secs).filter(|_| !lanes.pressed(arrow.direction)));
+
+        let end_time = arrow.end_time();
+        let finished = secs >= end_time;
+        if !finished && released.is_none() {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        let held_to_end = match released {
+            Some(release_secs) => release_secs >= end_time - HOLD_RELEASE_MARGIN,
+            None => finished,
+        };
+        if held_to_end {
+            let points = score.increase_hold_correct();
+            correct_arrow_events.send(CorrectArrowEvent {
+                direction: arrow.direction,
+                judgment: holding.0,
+                points,
+            });
+        } else {
+            score.increase_hold_fails();
+        }
+    }
+}
+
+/// Shrinks the tails of hold arrows as they're being held
+fn update_hold_tails(
+    clock: Res<SongClock>,
+    calibration: Res<Calibration>,
+    arrows: Query<&Arrow, With<Holding>>,
+    mut tails: Query<(&Parent, &mut Sprite, &mut Transform), With<HoldTail>>,
+) {
+    for (parent, mut sprite, mut transform) in tails.iter_mut() {
+        if let Ok(arrow) = arrows.get(parent.0) {
+            let length = ((arrow.end_time() - clock.seconds() - calibration.visual_secs()) as f32
+                * arrow.speed.value())
+            .max(0.);
+            sprite.size.x = length;
+            *transform = tail_transform(arrow.direction, length);
+        }
     }
 }
 
 struct TargetArrow;
 
+/// Despawns every arrow and restores the arrows of the song, so it can be played again
+#[allow(clippy::type_complexity)]
+fn teardown_arrows(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<Arrow>, With<TargetArrow>)>>,
+    mut song_config: ResMut<SongConfig>,
+    charts: Res<Assets<SongConfigToml>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    match charts.get(&song_config.file) {
+        Some(chart) => {
+            song_config.arrows = load_arrows(chart, song_config.difficulty, song_config.offset)
+        }
+        None => error!("Chart of {} was unloaded", song_config.path),
+    }
+}
+
 fn setup_target_arrows(mut commands: Commands, materials: Res<ArrowMaterialResource>) {
     use Directions::*;
     let directions = [Up, Down, Left, Right];
@@ -160,7 +376,7 @@ fn setup_target_arrows(mut commands: Commands, materials: Res<ArrowMaterialResou
         transform.rotate(Quat::from_rotation_z(direction.rotation()));
         commands
             .spawn_bundle(SpriteBundle {
-                material: materials.border_texture.clone(),
+                material: materials.border_material(),
                 sprite: Sprite::new(Vec2::new(140., 140.)),
                 transform,
                 ..Default::default()
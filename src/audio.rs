@@ -1,19 +1,357 @@
-use crate::{consts::AppState, types::SongConfig};
-use bevy::prelude::*;
+use crate::{
+    calibration::Calibration,
+    clock::{SongClock, SongClockLabel},
+    consts::{AppState, PREVIEW_FADE, PREVIEW_LENGTH},
+    types::SongConfig,
+};
+use bevy::{asset::LoadState, audio::Decodable, prelude::*};
+use rodio::{
+    source::{SineWave, Zero},
+    OutputStream, OutputStreamHandle, Sample, Sink, Source,
+};
+use std::time::Duration;
 
 pub struct AudioPlugin;
 impl Plugin for AudioPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(SystemSet::on_update(AppState::Game).with_system(start_song.system()));
+        let (stream, stream_handle) = match OutputStream::try_default() {
+            Ok((stream, handle)) => (Some(stream), Some(handle)),
+            Err(err) => {
+                warn!("Couldn't open audio output, songs will be silent: {}", err);
+                (None, None)
+            }
+        };
+
+        app.insert_non_send_resource(SongOutput(stream))
+            .insert_resource(PreviewPlayer::new(stream_handle.clone()))
+            .insert_resource(SongPlayer::new(stream_handle))
+            .add_system(play_queued_song.system())
+            .add_system(update_preview.system())
+            .add_system_set(SystemSet::on_exit(AppState::Menu).with_system(stop_preview.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(start_song.system().after(SongClockLabel)),
+            )
+            .add_system_set(SystemSet::on_pause(AppState::Game).with_system(pause_song.system()))
+            .add_system_set(SystemSet::on_resume(AppState::Game).with_system(resume_song.system()))
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(stop_song.system()));
+    }
+}
+
+/// Keeps the output stream of the [SongPlayer] alive
+struct SongOutput(#[allow(dead_code)] Option<OutputStream>);
+
+/// Plays a single song at a time. Unlike [Audio], playback can be stopped
+pub struct SongPlayer {
+    stream_handle: Option<OutputStreamHandle>,
+    sink: Option<Sink>,
+    queued: Option<(Handle<AudioSource>, Duration)>,
+    paused: bool,
+}
+
+impl SongPlayer {
+    fn new(stream_handle: Option<OutputStreamHandle>) -> Self {
+        Self {
+            stream_handle,
+            sink: None,
+            queued: None,
+            paused: false,
+        }
+    }
+
+    /// Replaces the current song, playback starts as soon as the audio is loaded
+    pub fn play(&mut self, song: Handle<AudioSource>) {
+        self.play_from(song, 0.);
+    }
+
+    /// Like [SongPlayer::play], but skips the first `secs` seconds of the song
+    pub fn play_from(&mut self, song: Handle<AudioSource>, secs: f64) {
+        self.stop();
+        self.queued = Some((song, Duration::from_secs_f64(secs.max(0.))));
+    }
+
+    /// Checks if a song is playing or about to start playing
+    pub fn is_playing(&self) -> bool {
+        self.queued.is_some() || self.sink.as_ref().is_some_and(|sink| !sink.empty())
+    }
+
+    pub fn stop(&mut self) {
+        self.queued = None;
+        self.paused = false;
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+
+    /// Pauses the current song, a song that is still loading starts paused
+    pub fn pause(&mut self) {
+        self.paused = true;
+        if let Some(sink) = &self.sink {
+            sink.pause();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        if let Some(sink) = &self.sink {
+            sink.play();
+        }
+    }
+
+    /// Replaces the current song with a click at the start of every `beat`, starting right away
+    pub fn play_metronome(&mut self, beat: Duration) {
+        self.stop();
+        let click = SineWave::new(1000)
+            .take_duration(Duration::from_millis(30))
+            .amplify(0.3);
+        let metronome = Zero::<f32>::new(1, 48000)
+            .take_duration(beat)
+            .mix(click)
+            .buffered()
+            .repeat_infinite();
+        self.start_source(metronome);
+    }
+
+    fn start(&mut self, source: &AudioSource, skip: Duration) {
+        self.start_source(source.decoder().skip_duration(skip));
+    }
+
+    fn start_source<S>(&mut self, source: S)
+    where
+        S: Source + Send + 'static,
+        S::Item: Sample + Send,
+    {
+        let stream_handle = match &self.stream_handle {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        match Sink::try_new(stream_handle) {
+            Ok(sink) => {
+                sink.append(source);
+                if self.paused {
+                    sink.pause();
+                }
+                self.sink = Some(sink);
+            }
+            Err(err) => error!("Couldn't play song: {}", err),
+        }
+    }
+}
+
+/// Plays a part of a song, looping it with fades at both ends. Used by the menu to preview songs
+pub struct PreviewPlayer {
+    stream_handle: Option<OutputStreamHandle>,
+    sink: Option<Sink>,
+    preview: Option<Preview>,
+    /// Seconds played of the current loop of the preview
+    elapsed: f32,
+    /// Volume while the preview fades out before stopping
+    fading_out: Option<f32>,
+    /// Preview started once the current one has faded out
+    next: Option<Preview>,
+}
+
+struct Preview {
+    song: Handle<AudioSource>,
+    start: f64,
+    length: f64,
+}
+
+impl PreviewPlayer {
+    fn new(stream_handle: Option<OutputStreamHandle>) -> Self {
+        Self {
+            stream_handle,
+            sink: None,
+            preview: None,
+            elapsed: 0.,
+            fading_out: None,
+            next: None,
+        }
+    }
+
+    /// Previews `song` from `start` to `end` seconds, unless it's already being previewed.
+    /// Without an end, [PREVIEW_LENGTH] seconds are played. A preview that's playing fades out first
+    pub fn play(&mut self, song: Handle<AudioSource>, start: f64, end: Option<f64>) {
+        let playing = self
+            .preview
+            .as_ref()
+            .is_some_and(|preview| preview.song == song);
+        let next = self.next.as_ref().is_some_and(|next| next.song == song);
+        if (playing && self.fading_out.is_none()) || next {
+            return;
+        }
+
+        let preview = Preview {
+            song,
+            start,
+            length: end.map_or(PREVIEW_LENGTH, |end| end - start),
+        };
+        match &self.sink {
+            Some(sink) => {
+                if self.fading_out.is_none() {
+                    self.fading_out = Some(sink.volume());
+                }
+                self.next = Some(preview);
+            }
+            None => {
+                self.stop_now();
+                self.preview = Some(preview);
+            }
+        }
+    }
+
+    /// Fades out the preview, then stops it
+    pub fn stop(&mut self) {
+        self.next = None;
+        match &self.sink {
+            Some(sink) if self.fading_out.is_none() => self.fading_out = Some(sink.volume()),
+            Some(_) => {}
+            None => self.preview = None,
+        }
+    }
+
+    pub fn stop_now(&mut self) {
+        self.preview = None;
+        self.fading_out = None;
+        self.next = None;
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+
+    fn start(&mut self, source: &AudioSource) {
+        let (stream_handle, preview) = match (&self.stream_handle, &self.preview) {
+            (Some(handle), Some(preview)) => (handle, preview),
+            _ => return,
+        };
+
+        match Sink::try_new(stream_handle) {
+            Ok(sink) => {
+                sink.set_volume(0.);
+                sink.append(
+                    source
+                        .decoder()
+                        .skip_duration(Duration::from_secs_f64(preview.start.max(0.)))
+                        .take_duration(Duration::from_secs_f64(preview.length.max(0.))),
+                );
+                self.sink = Some(sink);
+                self.elapsed = 0.;
+            }
+            Err(err) => {
+                error!("Couldn't play preview: {}", err);
+                self.preview = None;
+            }
+        }
     }
 }
 
-fn start_song(audio: Res<Audio>, time: Res<Time>, config: Res<SongConfig>) {
-    // Song starts 3 seconds after real time
-    let secs = time.seconds_since_startup();
-    let secs_last = secs - time.delta_seconds_f64();
+/// Starts the preview once its audio is loaded, restarts it when it ends, and fades it
+fn update_preview(
+    mut player: ResMut<PreviewPlayer>,
+    audio_sources: Res<Assets<AudioSource>>,
+    time: Res<Time>,
+) {
+    let player = &mut *player;
+    let length = match &player.preview {
+        Some(preview) => preview.length as f32,
+        None => return,
+    };
 
-    if secs_last <= 3. && 3. <= secs {
-        audio.play(config.song_audio.clone());
+    let sink = match &player.sink {
+        Some(sink) => sink,
+        None => {
+            let source = player
+                .preview
+                .as_ref()
+                .and_then(|preview| audio_sources.get(&preview.song))
+                .cloned();
+            if let Some(source) = source {
+                player.start(&source);
+            }
+            return;
+        }
+    };
+
+    if let Some(volume) = player.fading_out {
+        let volume = volume - time.delta_seconds() / PREVIEW_FADE;
+        if volume <= 0. {
+            let next = player.next.take();
+            player.stop_now();
+            player.preview = next;
+        } else {
+            sink.set_volume(volume);
+            player.fading_out = Some(volume);
+        }
+        return;
+    }
+
+    if sink.empty() {
+        // Loops the preview
+        player.sink = None;
+        return;
     }
+
+    player.elapsed += time.delta_seconds();
+    let volume = (player.elapsed / PREVIEW_FADE)
+        .min((length - player.elapsed) / PREVIEW_FADE)
+        .clamp(0., 1.);
+    sink.set_volume(volume);
+}
+
+fn stop_preview(mut player: ResMut<PreviewPlayer>) {
+    player.stop_now();
+}
+
+fn play_queued_song(
+    mut player: ResMut<SongPlayer>,
+    audio_sources: Res<Assets<AudioSource>>,
+    asset_server: Res<AssetServer>,
+) {
+    let (source, skip) = match &player.queued {
+        Some((handle, skip)) => {
+            // Drops a song that won't ever load, so the game doesn't wait for it to end
+            if asset_server.get_load_state(handle) == LoadState::Failed {
+                match asset_server.get_handle_path(handle) {
+                    Some(path) => error!("Couldn't load song audio {:?}", path.path()),
+                    None => error!("Couldn't load song audio"),
+                }
+                player.queued = None;
+                return;
+            }
+            (audio_sources.get(handle), *skip)
+        }
+        None => return,
+    };
+
+    // Wait until the audio has loaded
+    if let Some(source) = source {
+        let source = source.clone();
+        player.queued = None;
+        player.start(&source, skip);
+    }
+}
+
+fn start_song(
+    mut player: ResMut<SongPlayer>,
+    clock: Res<SongClock>,
+    config: Res<SongConfig>,
+    calibration: Res<Calibration>,
+) {
+    // Song time 0. is when the audio is heard, after the lead-in
+    if clock.just_passed(calibration.audio_start(&clock)) {
+        player.play(config.song_audio.clone());
+    }
+}
+
+fn stop_song(mut player: ResMut<SongPlayer>) {
+    player.stop();
+}
+
+fn pause_song(mut player: ResMut<SongPlayer>) {
+    player.pause();
+}
+
+fn resume_song(mut player: ResMut<SongPlayer>) {
+    player.resume();
 }
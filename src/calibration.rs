@@ -0,0 +1,349 @@
+use crate::{
+    arrow::ArrowMaterialResource,
+    audio::SongPlayer,
+    clock::SongClock,
+    consts::*,
+    lane_input::LaneEvent,
+    menu::{button_color_system, ButtonMaterials},
+    types::{Directions, Speed},
+};
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Offsets measured by the calibration screen, in milliseconds
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Calibration {
+    /// How late the audio is heard. Songs start this much earlier to make up for it
+    pub audio_offset: f64,
+    /// How late the screen is seen. Arrows are drawn this much earlier to make up for it
+    pub visual_offset: f64,
+}
+
+impl Calibration {
+    pub fn audio_secs(&self) -> f64 {
+        self.audio_offset / 1000.
+    }
+
+    pub fn visual_secs(&self) -> f64 {
+        self.visual_offset / 1000.
+    }
+
+    /// Song time at which the audio starts playing, for it to be heard at 0.
+    /// Never before the lead-in, which would keep it from starting
+    pub fn audio_start(&self, clock: &SongClock) -> f64 {
+        (-self.audio_secs()).max(-clock.lead_in())
+    }
+}
+
+/// Screen measuring the [Calibration], opened from the menu
+pub struct CalibrationPlugin;
+impl Plugin for CalibrationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::Calibration).with_system(setup_calibration.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Calibration)
+                .with_system(button_color_system.system())
+                .with_system(calibration_button_system.system())
+                .with_system(collect_taps.system())
+                .with_system(move_test_arrow.system())
+                .with_system(update_calibration_text.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Calibration).with_system(despawn_calibration.system()),
+        );
+    }
+}
+
+struct CalibrationUI;
+
+struct CalibrationText;
+
+/// Sprite of the visual test. The arrow reaches the target on every beat
+struct TestArrow {
+    moving: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TestKind {
+    /// Tapping along with a metronome
+    Audio,
+    /// Tapping when an arrow reaches its target, without sound
+    Visual,
+}
+
+/// Test being run, only present while it runs
+struct CalibrationTest {
+    kind: TestKind,
+    /// When the first beat happened
+    start: Instant,
+    /// Milliseconds between each tap and its beat
+    offsets: Vec<f64>,
+}
+
+enum CalibrationButton {
+    Test(TestKind),
+    Back,
+}
+
+fn beat_secs() -> f64 {
+    60. / CALIBRATION_BPM
+}
+
+fn calibration_text(calibration: &Calibration, test: Option<&CalibrationTest>) -> String {
+    let offsets = format!(
+        "Audio offset: {:.0} ms\nVisual offset: {:.0} ms",
+        calibration.audio_offset, calibration.visual_offset
+    );
+
+    match test {
+        Some(test) => {
+            let instructions = match test.kind {
+                TestKind::Audio => "Press any lane on each click",
+                TestKind::Visual => "Press any lane when the arrow reaches its target",
+            };
+            format!(
+                "{}\n{}: {}/{}",
+                offsets,
+                instructions,
+                test.offsets.len(),
+                CALIBRATION_TAPS
+            )
+        }
+        None => format!("{}\nPick a test", offsets),
+    }
+}
+
+fn setup_calibration(
+    mut commands: Commands,
+    button_materials: Res<ButtonMaterials>,
+    arrow_materials: Res<ArrowMaterialResource>,
+    calibration: Res<Calibration>,
+) {
+    let text_style = TextStyle {
+        font: button_materials.font.clone(),
+        font_size: 20.,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::FlexStart,
+                ..Default::default()
+            },
+            material: button_materials.none.clone(),
+            ..Default::default()
+        })
+        .insert(CalibrationUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        calibration_text(&calibration, None),
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(CalibrationText);
+
+            let buttons = [
+                (CalibrationButton::Test(TestKind::Audio), "Audio test"),
+                (CalibrationButton::Test(TestKind::Visual), "Visual test"),
+                (CalibrationButton::Back, "Back"),
+            ];
+            for (button, text) in buttons {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(350.0), Val::Px(50.0)),
+                            margin: Rect::all(Val::Px(10.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(text, text_style.clone(), Default::default()),
+                            ..Default::default()
+                        });
+                    })
+                    .insert(button);
+            }
+        });
+
+    // Target and arrow of the visual test, hidden until it runs
+    let y = CALIBRATION_ARROW_Y;
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: arrow_materials.border_material(),
+            sprite: Sprite::new(Vec2::new(140., 140.)),
+            transform: Transform::from_translation(Vec3::new(TARGET_POSITION, y, 1.)),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(CalibrationUI)
+        .insert(TestArrow { moving: false });
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: arrow_materials.speed_material(Speed::Medium),
+            sprite: Sprite::new(Vec2::new(140., 140.)),
+            transform: Transform::from_translation(Vec3::new(SPAWN_POSITION, y, 2.)),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(CalibrationUI)
+        .insert(TestArrow { moving: true });
+}
+
+#[allow(clippy::type_complexity)]
+fn calibration_button_system(
+    mut commands: Commands,
+    query: Query<(&Interaction, &CalibrationButton), (Changed<Interaction>, With<Button>)>,
+    mut player: ResMut<SongPlayer>,
+    mut state: ResMut<State<AppState>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            CalibrationButton::Test(kind) => {
+                match kind {
+                    TestKind::Audio => player.play_metronome(Duration::from_secs_f64(beat_secs())),
+                    TestKind::Visual => player.stop(),
+                }
+                commands.insert_resource(CalibrationTest {
+                    kind: *kind,
+                    start: Instant::now(),
+                    offsets: Vec::new(),
+                });
+            }
+            CalibrationButton::Back => state
+                .set(AppState::Menu)
+                .expect("Couldn't switch state to Menu"),
+        }
+    }
+}
+
+/// Middle value of `values`, which taps too early or too late barely move
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values[values.len() / 2]
+}
+
+/// Measures the presses of the running test against its beats, and sets its offset once it has enough
+fn collect_taps(
+    mut commands: Commands,
+    mut lane_events: EventReader<LaneEvent>,
+    test: Option<ResMut<CalibrationTest>>,
+    mut calibration: ResMut<Calibration>,
+    mut player: ResMut<SongPlayer>,
+) {
+    let mut test = match test {
+        Some(test) => test,
+        None => {
+            // Presses from before the test don't count
+            lane_events.iter().for_each(drop);
+            return;
+        }
+    };
+
+    let beat = beat_secs();
+    for event in lane_events.iter().filter(|event| event.pressed) {
+        if event.time < test.start {
+            continue;
+        }
+
+        // Each press belongs to the closest beat
+        let secs = (event.time - test.start).as_secs_f64();
+        let offset = secs - (secs / beat).round() * beat;
+        test.offsets.push(offset * 1000.);
+    }
+
+    if test.offsets.len() < CALIBRATION_TAPS {
+        return;
+    }
+
+    let offset = median(&mut test.offsets).round();
+    match test.kind {
+        TestKind::Audio => calibration.audio_offset = offset,
+        TestKind::Visual => calibration.visual_offset = offset,
+    }
+    player.stop();
+    commands.remove_resource::<CalibrationTest>();
+}
+
+/// Moves the arrow of the visual test so it reaches its target on every beat
+fn move_test_arrow(
+    time: Res<Time>,
+    test: Option<Res<CalibrationTest>>,
+    mut query: Query<(&mut Transform, &mut Visible, &TestArrow)>,
+) {
+    let elapsed = match (&test, time.last_update()) {
+        (Some(test), Some(now)) if test.kind == TestKind::Visual && now >= test.start => {
+            Some((now - test.start).as_secs_f64())
+        }
+        _ => None,
+    };
+
+    let beat = beat_secs();
+    for (mut transform, mut visible, test_arrow) in query.iter_mut() {
+        let is_visible = elapsed.is_some();
+        if visible.is_visible != is_visible {
+            visible.is_visible = is_visible;
+        }
+
+        if !test_arrow.moving {
+            continue;
+        }
+        if let Some(elapsed) = elapsed {
+            let until_beat = beat - elapsed % beat;
+            transform.translation.x = TARGET_POSITION - until_beat as f32 * Speed::Medium.value();
+            transform.rotation = Quat::from_rotation_z(Directions::Right.rotation());
+        }
+    }
+}
+
+fn update_calibration_text(
+    calibration: Res<Calibration>,
+    test: Option<Res<CalibrationTest>>,
+    mut query: Query<&mut Text, With<CalibrationText>>,
+) {
+    let value = calibration_text(&calibration, test.as_deref());
+    for mut text in query.iter_mut() {
+        // Only setting changed text, so it isn't laid out again every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn despawn_calibration(
+    mut commands: Commands,
+    query: Query<Entity, With<CalibrationUI>>,
+    mut player: ResMut<SongPlayer>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<CalibrationTest>();
+    player.stop();
+}
@@ -0,0 +1,95 @@
+use crate::{
+    clock::{SongClock, SongClockLabel},
+    consts::AppState,
+    types::{load_arrows, parse_config_toml, song_file_path, SongConfig, SongConfigToml},
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use std::path::Path;
+
+/// Loads `.toml` charts as [SongConfigToml] assets, and reloads them while playing when they change
+pub struct ChartLoaderPlugin;
+impl Plugin for ChartLoaderPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<SongConfigToml>()
+            .init_asset_loader::<ChartLoader>()
+            .add_startup_system(watch_charts.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(reload_chart.system().after(SongClockLabel)),
+            );
+    }
+}
+
+#[derive(Default)]
+pub struct ChartLoader;
+
+impl AssetLoader for ChartLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            // Errors name the file relative to `assets/songs`, like the rest of the charts
+            let path = load_context.path();
+            let path = path
+                .strip_prefix("songs")
+                .unwrap_or(path)
+                .display()
+                .to_string();
+
+            let contents = std::str::from_utf8(bytes)?;
+            let config = parse_config_toml(&path, contents)?;
+
+            // The audio starts loading along with the chart
+            let audio_path = Path::new("songs").join(song_file_path(&path, &config.filename));
+            load_context
+                .set_default_asset(LoadedAsset::new(config).with_dependency(audio_path.into()));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["toml"]
+    }
+}
+
+fn watch_charts(asset_server: Res<AssetServer>) {
+    if let Err(err) = asset_server.watch_for_changes() {
+        warn!("Charts won't be reloaded when they change: {}", err);
+    }
+}
+
+/// Replaces the arrows that haven't spawned yet when the chart being played changes on disk
+fn reload_chart(
+    mut events: EventReader<AssetEvent<SongConfigToml>>,
+    charts: Res<Assets<SongConfigToml>>,
+    mut song_config: ResMut<SongConfig>,
+    clock: Res<SongClock>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if *handle != song_config.file {
+                continue;
+            }
+
+            if let Some(chart) = charts.get(handle) {
+                let secs = clock.seconds();
+                song_config.name = chart.name.clone();
+                song_config.metadata = chart.metadata.clone();
+                song_config.rating = chart
+                    .chart(song_config.difficulty)
+                    .map_or(0, |chart| chart.rating);
+                song_config.arrows = load_arrows(chart, song_config.difficulty, song_config.offset)
+                    .into_iter()
+                    .filter(|arrow| arrow.spawn_time >= secs)
+                    .collect();
+                info!("Reloaded {}", song_config.path);
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::consts::*;
+use bevy::prelude::*;
+use std::time::Instant;
+
+pub struct ClockPlugin;
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SongClock>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::Game)
+                    .with_system(reset_song_clock.system().label(SongClockLabel)),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(tick_song_clock.system().label(SongClockLabel)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::MakeMap)
+                    .with_system(reset_song_clock.system().label(SongClockLabel)),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MakeMap)
+                    .with_system(tick_song_clock.system().label(SongClockLabel)),
+            );
+    }
+}
+
+/// Label of the systems resetting and advancing the [SongClock], systems using it should run after them
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SongClockLabel;
+
+/// Position in the song, in seconds. Negative during the lead-in, 0. is when the audio starts
+pub struct SongClock {
+    lead_in: f64,
+    secs: f64,
+    secs_last: f64,
+    paused: bool,
+    /// When the last tick happened, which `secs` is the song time of
+    ticked_at: Option<Instant>,
+}
+
+impl Default for SongClock {
+    fn default() -> Self {
+        Self::new(LEAD_IN)
+    }
+}
+
+impl SongClock {
+    pub fn new(lead_in: f64) -> Self {
+        Self {
+            lead_in,
+            secs: -lead_in,
+            secs_last: -lead_in,
+            paused: false,
+            ticked_at: None,
+        }
+    }
+
+    /// Rewinds the clock to the start of the lead-in and resumes it
+    pub fn reset(&mut self) {
+        self.seek(-self.lead_in);
+        self.paused = false;
+    }
+
+    /// Advances the clock by `delta` seconds, unless it is paused
+    pub fn tick(&mut self, delta: f64) {
+        self.secs_last = self.secs;
+        if !self.paused {
+            self.secs += delta;
+        }
+    }
+
+    /// Jumps to `secs` without passing through the time in between
+    pub fn seek(&mut self, secs: f64) {
+        self.secs = secs;
+        self.secs_last = secs;
+        self.ticked_at = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn lead_in(&self) -> f64 {
+        self.lead_in
+    }
+
+    /// Current song time
+    pub fn seconds(&self) -> f64 {
+        self.secs
+    }
+
+    /// Song time at `instant`, going from the last tick at the pace of the clock
+    pub fn seconds_at(&self, instant: Instant) -> f64 {
+        match self.ticked_at {
+            Some(ticked_at) if !self.paused => {
+                if instant >= ticked_at {
+                    self.secs + (instant - ticked_at).as_secs_f64()
+                } else {
+                    self.secs - (ticked_at - instant).as_secs_f64()
+                }
+            }
+            _ => self.secs,
+        }
+    }
+
+    /// Song time before the last tick
+    pub fn seconds_last(&self) -> f64 {
+        self.secs_last
+    }
+
+    /// Checks if `secs` was reached during the last tick
+    pub fn just_passed(&self, secs: f64) -> bool {
+        self.secs_last <= secs && secs < self.secs
+    }
+}
+
+fn reset_song_clock(mut clock: ResMut<SongClock>) {
+    clock.reset();
+}
+
+fn tick_song_clock(time: Res<Time>, mut clock: ResMut<SongClock>) {
+    clock.tick(time.delta_seconds_f64());
+    clock.ticked_at = time.last_update();
+}
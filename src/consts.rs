@@ -7,12 +7,97 @@ pub const SPAWN_POSITION: f32 = -400.;
 /// X coordinates value where the arrows should should be clicked
 pub const TARGET_POSITION: f32 = 200.;
 
-/// Margin of error for clicking an arrow
+/// Distance past the target after which a missed arrow starts falling off
 pub const THRESHOLD: f32 = 24.;
 
+/// Judgment windows, in milliseconds before or after the click time of an arrow.
+/// Clicking outside of the widest window doesn't click the arrow at all
+pub const PERFECT_WINDOW: f64 = 25.;
+pub const GREAT_WINDOW: f64 = 50.;
+pub const GOOD_WINDOW: f64 = 90.;
+pub const BAD_WINDOW: f64 = 135.;
+
+/// Combo needed for each increase of the score multiplier
+pub const COMBO_MULTIPLIER_STEP: usize = 10;
+
+/// Increase of the score multiplier every [COMBO_MULTIPLIER_STEP] combo
+pub const COMBO_MULTIPLIER_INCREMENT: f32 = 0.25;
+
+/// Highest score multiplier a combo can reach
+pub const MAX_COMBO_MULTIPLIER: f32 = 2.;
+
+/// Seconds before its end at which a hold arrow can be released without failing it
+pub const HOLD_RELEASE_MARGIN: f64 = 0.1;
+
+/// Points for holding a hold arrow until its end
+pub const HOLD_POINTS: usize = 100;
+
+/// Shortest hold arrow, in seconds, shorter holds are recorded as normal arrows
+pub const MIN_HOLD_DURATION: f64 = 0.25;
+
+/// Width of the tail of hold arrows
+pub const HOLD_TAIL_WIDTH: f32 = 40.;
+
 /// Total distance traveled by an arrow, from spawn to target
 pub const DISTANCE: f32 = TARGET_POSITION - SPAWN_POSITION;
 
+/// Seconds between entering a song and the start of its audio
+pub const LEAD_IN: f64 = 3.;
+
+/// File the high scores are saved in
+pub const HIGH_SCORES_PATH: &str = "save/high_scores.toml";
+
+/// File the per-song offsets are saved in
+pub const SONG_OFFSETS_PATH: &str = "save/song_offsets.toml";
+
+/// Milliseconds a per-song offset moves by on each press of its hotkeys
+pub const SONG_OFFSET_STEP: f64 = 5.;
+
+/// File the player settings are saved in
+pub const SETTINGS_PATH: &str = "save/settings.toml";
+
+/// Packs and songs listed on each page of the menu
+pub const MENU_PAGE_SIZE: usize = 4;
+
+/// File the songs found by the last library scan are saved in
+pub const LIBRARY_CACHE_PATH: &str = "save/library.toml";
+
+/// Seconds counted down before a paused song resumes
+pub const RESUME_COUNTDOWN: f32 = 3.;
+
+/// Seconds of song previewed in the menu, when the song doesn't say where its preview ends
+pub const PREVIEW_LENGTH: f64 = 15.;
+
+/// Seconds song previews take to fade in and out
+pub const PREVIEW_FADE: f32 = 1.;
+
+/// Tempo assumed for songs that don't declare one
+pub const DEFAULT_BPM: f64 = 120.;
+
+/// X coordinates value of the playback cursor in the chart editor
+pub const EDITOR_CURSOR_X: f32 = -200.;
+
+/// Pixels per second of song in the chart editor timeline
+pub const EDITOR_ZOOM: f32 = 300.;
+
+/// Size of the arrows drawn in the chart editor
+pub const EDITOR_ARROW_SIZE: f32 = 90.;
+
+/// Audio file extensions that can be played as songs
+pub const AUDIO_EXTENSIONS: [&str; 4] = ["mp3", "ogg", "wav", "flac"];
+
+/// How far a gamepad axis has to be pushed to press the lane bound to it
+pub const PAD_AXIS_THRESHOLD: f32 = 0.5;
+
+/// Tempo of the metronome and of the moving arrow of the calibration screen
+pub const CALIBRATION_BPM: f64 = 100.;
+
+/// Y coordinate of the arrow of the visual calibration test, below the buttons
+pub const CALIBRATION_ARROW_Y: f32 = -180.;
+
+/// Taps each calibration test collects before setting its offset
+pub const CALIBRATION_TAPS: usize = 16;
+
 /// Stage for our systems
 pub const APP_STATE_STAGE: &str = "app_state_stage";
 
@@ -22,4 +107,8 @@ pub enum AppState {
     Menu,
     Game,
     MakeMap,
+    Results,
+    Paused,
+    KeyBindings,
+    Calibration,
 }
@@ -0,0 +1,803 @@
+use crate::{
+    arrow::ArrowMaterialResource,
+    audio::SongPlayer,
+    calibration::Calibration,
+    clock::{SongClock, SongClockLabel},
+    consts::*,
+    lane_input::LaneEvent,
+    tempo::TempoMap,
+    types::{
+        load_config_toml, song_file_path, ArrowTimeToml, Difficulty, Directions, SongConfigToml,
+        Speed,
+    },
+};
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use std::{fs, mem};
+
+pub struct EditorPlugin;
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<EditorMaterials>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::MakeMap)
+                    .with_system(setup_editor.system().after(SongClockLabel)),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MakeMap)
+                    .with_system(toggle_playback.system().label(EditorInput))
+                    .with_system(scrub_timeline.system().label(EditorInput))
+                    .with_system(edit_arrows.system().label(EditorInput))
+                    .with_system(save_chart.system().label(EditorInput))
+                    .with_system(
+                        draw_timeline
+                            .system()
+                            .after(EditorInput)
+                            .after(SongClockLabel),
+                    )
+                    .with_system(update_editor_text.system().after(EditorInput)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MakeMap).with_system(despawn_editor.system()),
+            );
+    }
+}
+
+/// Beat subdivisions the editor can snap to
+const SNAP_DIVISIONS: [u32; 8] = [1, 2, 3, 4, 6, 8, 12, 16];
+
+/// Maximum number of edits that can be undone
+const UNDO_LIMIT: usize = 200;
+
+/// Arrows closer than this, in seconds, are considered to be at the same time
+const SAME_TIME_EPSILON: f64 = 0.001;
+
+/// Chart to edit, inserted by the menu before entering [AppState::MakeMap]
+pub struct EditMap {
+    /// File name of the song, relative to `assets/songs`
+    pub path: String,
+    pub difficulty: Difficulty,
+}
+
+#[derive(SystemLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct EditorInput;
+
+struct Editor {
+    path: String,
+    config: SongConfigToml,
+    /// Index of the chart being edited in the charts of the song
+    chart: usize,
+    song_audio: Handle<AudioSource>,
+    /// Index into [SNAP_DIVISIONS]
+    snap: usize,
+    speed: Speed,
+    selected: Option<usize>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    modified: bool,
+}
+
+/// What edits can change, saved before each edit for undo
+struct Snapshot {
+    bpm: Option<f64>,
+    arrows: Vec<ArrowTimeToml>,
+}
+
+impl Editor {
+    fn bpm(&self) -> f64 {
+        self.config.bpm.unwrap_or(DEFAULT_BPM)
+    }
+
+    fn snap_division(&self) -> u32 {
+        SNAP_DIVISIONS[self.snap]
+    }
+
+    fn arrows(&self) -> &[ArrowTimeToml] {
+        &self.config.charts[self.chart].arrows
+    }
+
+    fn tempo(&self) -> TempoMap {
+        self.config.tempo_map()
+    }
+
+    /// Seconds between two lines of the beat grid around `secs`
+    fn snap_length(&self, secs: f64) -> f64 {
+        let tempo = self.tempo();
+        60. / tempo.bpm(tempo.beat(secs)) / self.snap_division() as f64
+    }
+
+    /// Rounds `secs` to the nearest line of the beat grid
+    fn snap_time(&self, secs: f64) -> f64 {
+        self.step_time(secs, 0.)
+    }
+
+    /// Moves `lines` lines of the beat grid away from `secs`, landing on a line
+    fn step_time(&self, secs: f64, lines: f64) -> f64 {
+        self.tempo().seconds(self.step_beat(secs, lines))
+    }
+
+    /// Beat of the line of the beat grid `lines` lines away from `secs`
+    fn step_beat(&self, secs: f64, lines: f64) -> f64 {
+        let division = self.snap_division() as f64;
+        ((self.tempo().beat(secs) * division).round() + lines) / division
+    }
+
+    /// Whether the arrows of the chart are written in beats, new arrows are written the same way
+    fn uses_beats(&self) -> bool {
+        self.arrows().iter().any(|arrow| arrow.beat.is_some())
+    }
+
+    fn arrow_at(&self, secs: f64, direction: Directions) -> Option<usize> {
+        let tempo = self.tempo();
+        self.arrows().iter().position(|arrow| {
+            arrow.direction == direction && (arrow.seconds(&tempo) - secs).abs() < SAME_TIME_EPSILON
+        })
+    }
+
+    /// Saves the arrows and tempo for undo, before an edit
+    fn push_undo(&mut self) {
+        self.undo.push(Snapshot {
+            bpm: self.config.bpm,
+            arrows: self.config.charts[self.chart].arrows.clone(),
+        });
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.modified = true;
+    }
+
+    /// Applies `edit` to the arrows and the selection, saving the arrows before it for undo
+    fn edit(&mut self, edit: impl FnOnce(&mut Vec<ArrowTimeToml>, &mut Option<usize>)) {
+        self.push_undo();
+        edit(
+            &mut self.config.charts[self.chart].arrows,
+            &mut self.selected,
+        );
+        self.sort_arrows();
+    }
+
+    /// Changes the BPM, saving the one before it for undo
+    fn set_bpm(&mut self, bpm: f64) {
+        self.push_undo();
+        self.config.bpm = Some(bpm);
+    }
+
+    /// Puts back `snapshot`, returning what it replaced
+    fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+        self.selected = None;
+        self.modified = true;
+        Snapshot {
+            bpm: mem::replace(&mut self.config.bpm, snapshot.bpm),
+            arrows: mem::replace(&mut self.config.charts[self.chart].arrows, snapshot.arrows),
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            let current = self.restore(snapshot);
+            self.redo.push(current);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            let current = self.restore(snapshot);
+            self.undo.push(current);
+        }
+    }
+
+    /// Sorts the arrows by click time, keeping the same arrow selected
+    fn sort_arrows(&mut self) {
+        let tempo = self.tempo();
+        let arrows = &self.config.charts[self.chart].arrows;
+        let mut order: Vec<usize> = (0..arrows.len()).collect();
+        order.sort_by(|a, b| {
+            arrows[*a]
+                .seconds(&tempo)
+                .partial_cmp(&arrows[*b].seconds(&tempo))
+                .unwrap()
+        });
+
+        self.selected = self
+            .selected
+            .and_then(|selected| order.iter().position(|i| *i == selected));
+        self.config.charts[self.chart].arrows = order.iter().map(|i| arrows[*i].clone()).collect();
+    }
+
+    fn save(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(format!("assets/songs/{}", self.path), self.config.to_toml())?;
+        self.modified = false;
+        Ok(())
+    }
+}
+
+struct EditorMaterials {
+    none: Handle<ColorMaterial>,
+    measure_line: Handle<ColorMaterial>,
+    beat_line: Handle<ColorMaterial>,
+    snap_line: Handle<ColorMaterial>,
+    cursor: Handle<ColorMaterial>,
+    selection: Handle<ColorMaterial>,
+    font: Handle<Font>,
+}
+
+impl FromWorld for EditorMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        EditorMaterials {
+            none: materials.add(Color::NONE.into()),
+            measure_line: materials.add(Color::rgb(0.8, 0.8, 0.8).into()),
+            beat_line: materials.add(Color::rgb(0.5, 0.5, 0.5).into()),
+            snap_line: materials.add(Color::rgb(0.25, 0.25, 0.3).into()),
+            cursor: materials.add(Color::rgb(0.9, 0.2, 0.2).into()),
+            selection: materials.add(Color::rgba(1., 1., 0.4, 0.5).into()),
+            font,
+        }
+    }
+}
+
+struct EditorUI;
+struct EditorText;
+
+/// Entities drawn for the timeline, respawned whenever it changes
+struct TimelineEntity;
+
+impl EditorText {
+    fn get_text(editor: &Editor, secs: f64) -> String {
+        let tempo = editor.tempo();
+        let beat = tempo.beat(secs);
+        let (numerator, denominator) = tempo.time_signature(beat);
+        format!(
+            "Editing: {} ({:?}){}\nTime: {:.3}, Beat: {:.2}, BPM: {:.2}, {}/{}, Snap: 1/{}, Speed: {:?}, Arrows: {}",
+            editor.config.name,
+            editor.config.charts[editor.chart].difficulty,
+            if editor.modified { " *" } else { "" },
+            secs,
+            beat,
+            tempo.bpm(beat),
+            numerator,
+            denominator,
+            editor.snap_division(),
+            editor.speed,
+            editor.arrows().len(),
+        )
+    }
+}
+
+const HELP_TEXT: &str =
+    "Space: play/pause   Wheel , . PgUp PgDn Home: scrub   Arrows/WASD: add/remove\n\
+    Tab: select   Shift+direction: move lane   [ ]: retime   H/Shift+H: hold length   Del: delete   1/2/3: speed\n\
+    - =: snap   Ctrl+- =: BPM   Ctrl+Z/Y: undo/redo   Ctrl+S: save   Enter: save and exit";
+
+fn setup_editor(
+    mut commands: Commands,
+    edit_map: Option<Res<EditMap>>,
+    asset_server: Res<AssetServer>,
+    materials: Res<EditorMaterials>,
+    mut clock: ResMut<SongClock>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let edit_map = match edit_map {
+        Some(edit_map) => edit_map,
+        None => return,
+    };
+
+    let config = match load_config_toml(&edit_map.path) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Couldn't open the chart: {}", err);
+            state
+                .set(AppState::Menu)
+                .expect("Couldn't switch state to Menu");
+            return;
+        }
+    };
+    let chart = match config
+        .charts
+        .iter()
+        .position(|chart| chart.difficulty == edit_map.difficulty)
+    {
+        Some(chart) => chart,
+        None => {
+            error!(
+                "{} doesn't have a {:?} chart",
+                edit_map.path, edit_map.difficulty
+            );
+            state
+                .set(AppState::Menu)
+                .expect("Couldn't switch state to Menu");
+            return;
+        }
+    };
+    let song_audio = asset_server.load(&*format!(
+        "songs/{}",
+        song_file_path(&edit_map.path, &config.filename)
+    ));
+    let editor = Editor {
+        path: edit_map.path.clone(),
+        config,
+        chart,
+        song_audio,
+        snap: 3,
+        speed: Speed::Slow,
+        selected: None,
+        undo: Vec::new(),
+        redo: Vec::new(),
+        modified: false,
+    };
+
+    // The editor starts paused at the beginning of the song, with no lead-in
+    clock.seek(0.);
+    clock.pause();
+
+    let text_style = TextStyle {
+        font: materials.font.clone(),
+        font_size: 20.,
+        color: Color::rgb(0.8, 0.8, 0.8),
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::SpaceBetween,
+                padding: Rect::all(Val::Px(10.)),
+                ..Default::default()
+            },
+            material: materials.none.clone(),
+            ..Default::default()
+        })
+        .insert(EditorUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        EditorText::get_text(&editor, 0.),
+                        text_style.clone(),
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(EditorText);
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    HELP_TEXT,
+                    TextStyle {
+                        font_size: 14.,
+                        ..text_style
+                    },
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            });
+        });
+
+    commands.insert_resource(editor);
+}
+
+fn toggle_playback(
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Option<Res<Editor>>,
+    mut clock: ResMut<SongClock>,
+    mut player: ResMut<SongPlayer>,
+    calibration: Res<Calibration>,
+) {
+    let editor = match editor {
+        Some(editor) => editor,
+        None => return,
+    };
+
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        if clock.is_paused() {
+            clock.resume();
+            player.play_from(
+                editor.song_audio.clone(),
+                clock.seconds() + calibration.audio_secs(),
+            );
+        } else {
+            clock.pause();
+            player.stop();
+        }
+    }
+}
+
+fn scrub_timeline(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    editor: Option<ResMut<Editor>>,
+    mut clock: ResMut<SongClock>,
+    mut player: ResMut<SongPlayer>,
+    calibration: Res<Calibration>,
+) {
+    let mut editor = match editor {
+        Some(editor) => editor,
+        None => return,
+    };
+
+    let start = clock.seconds();
+    let mut secs = start;
+
+    for event in mouse_wheel.iter() {
+        secs -= match event.unit {
+            MouseScrollUnit::Line => event.y as f64 * editor.snap_length(secs),
+            MouseScrollUnit::Pixel => (event.y / EDITOR_ZOOM) as f64,
+        };
+    }
+
+    let tempo = editor.tempo();
+    let measure = editor.snap_division() as f64 * tempo.measure_length(tempo.beat(secs));
+    if keyboard_input.just_pressed(KeyCode::Comma) {
+        secs = editor.step_time(secs, -1.);
+    } else if keyboard_input.just_pressed(KeyCode::Period) {
+        secs = editor.step_time(secs, 1.);
+    } else if keyboard_input.just_pressed(KeyCode::PageUp) {
+        secs = editor.step_time(secs, -measure);
+    } else if keyboard_input.just_pressed(KeyCode::PageDown) {
+        secs = editor.step_time(secs, measure);
+    } else if keyboard_input.just_pressed(KeyCode::Home) {
+        secs = 0.;
+    }
+
+    // Tab walks through the arrows, moving the cursor to the selected one
+    if keyboard_input.just_pressed(KeyCode::Tab) && !editor.arrows().is_empty() {
+        let backwards =
+            keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+        let len = editor.arrows().len();
+        let selected = match editor.selected {
+            Some(selected) if backwards => (selected + len - 1) % len,
+            Some(selected) => (selected + 1) % len,
+            None => editor
+                .arrows()
+                .iter()
+                .position(|arrow| arrow.seconds(&tempo) >= secs - SAME_TIME_EPSILON)
+                .unwrap_or(0),
+        };
+        editor.selected = Some(selected);
+        secs = editor.arrows()[selected].seconds(&tempo);
+    }
+
+    let secs = secs.max(0.);
+    if (secs - start).abs() > f64::EPSILON {
+        clock.seek(secs);
+        if !clock.is_paused() {
+            player.play_from(editor.song_audio.clone(), secs + calibration.audio_secs());
+        }
+    }
+}
+
+fn edit_arrows(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut lane_events: EventReader<LaneEvent>,
+    editor: Option<ResMut<Editor>>,
+    clock: Res<SongClock>,
+) {
+    // Read before returning, so presses aren't left for the next frame
+    let presses: Vec<_> = lane_events
+        .iter()
+        .filter(|event| event.pressed)
+        .map(|event| event.direction)
+        .collect();
+    let mut editor = match editor {
+        Some(editor) => editor,
+        None => return,
+    };
+
+    let ctrl =
+        keyboard_input.pressed(KeyCode::LControl) || keyboard_input.pressed(KeyCode::RControl);
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+
+    if ctrl {
+        if keyboard_input.just_pressed(KeyCode::Z) {
+            if shift {
+                editor.redo();
+            } else {
+                editor.undo();
+            }
+        } else if keyboard_input.just_pressed(KeyCode::Y) {
+            editor.redo();
+        } else if keyboard_input.just_pressed(KeyCode::Minus) {
+            let bpm = (editor.bpm() - 1.).max(1.);
+            editor.set_bpm(bpm);
+        } else if keyboard_input.just_pressed(KeyCode::Equals) {
+            let bpm = editor.bpm() + 1.;
+            editor.set_bpm(bpm);
+        }
+        // Every other shortcut with Ctrl belongs to another system
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        editor.snap = editor.snap.saturating_sub(1);
+    } else if keyboard_input.just_pressed(KeyCode::Equals) {
+        editor.snap = (editor.snap + 1).min(SNAP_DIVISIONS.len() - 1);
+    }
+
+    let speed = if keyboard_input.just_pressed(KeyCode::Key1) {
+        Some(Speed::Slow)
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        Some(Speed::Medium)
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        Some(Speed::Fast)
+    } else {
+        None
+    };
+    if let Some(speed) = speed {
+        editor.speed = speed;
+        if let Some(selected) = editor.selected {
+            editor.edit(|arrows, _| arrows[selected].speed = speed);
+        }
+    }
+
+    if let Some(selected) = editor.selected {
+        if keyboard_input.just_pressed(KeyCode::Delete)
+            || keyboard_input.just_pressed(KeyCode::Back)
+        {
+            editor.edit(|arrows, selection| {
+                arrows.remove(selected);
+                *selection = None;
+            });
+            return;
+        }
+
+        let lines = if keyboard_input.just_pressed(KeyCode::LBracket) {
+            -1.
+        } else if keyboard_input.just_pressed(KeyCode::RBracket) {
+            1.
+        } else {
+            0.
+        };
+        if lines != 0. {
+            let tempo = editor.tempo();
+            let mut arrow = editor.arrows()[selected].clone();
+            let beat = editor.step_beat(arrow.seconds(&tempo), lines);
+            // Arrows can't go before the start of the song
+            if tempo.seconds(beat) >= 0. {
+                arrow.set_beat(beat, &tempo);
+            } else {
+                arrow.set_beat(tempo.beat(0.), &tempo);
+            }
+            editor.edit(|arrows, _| arrows[selected] = arrow);
+        }
+
+        // H lengthens the hold by a line of the beat grid, Shift+H shortens it
+        if keyboard_input.just_pressed(KeyCode::H) {
+            let click_time = editor.arrows()[selected].seconds(&editor.tempo());
+            let duration = editor.arrows()[selected].duration.unwrap_or(0.);
+            let lines = if shift { -1. } else { 1. };
+            let end_time = editor.step_time(click_time + duration, lines);
+            let duration = Some(end_time - click_time).filter(|duration| *duration > 0.);
+            editor.edit(|arrows, _| arrows[selected].duration = duration);
+        }
+    }
+
+    for direction in presses {
+        if shift {
+            if let Some(selected) = editor.selected {
+                editor.edit(|arrows, _| arrows[selected].direction = direction);
+            }
+            continue;
+        }
+
+        let click_time = editor.snap_time(clock.seconds()).max(0.);
+        let speed = editor.speed;
+        let mut arrow = ArrowTimeToml {
+            click_time,
+            beat: None,
+            speed,
+            direction,
+            duration: None,
+        };
+        if editor.uses_beats() {
+            let tempo = editor.tempo();
+            arrow.set_beat(tempo.beat(click_time), &tempo);
+        }
+        match editor.arrow_at(click_time, direction) {
+            Some(index) => editor.edit(|arrows, selection| {
+                arrows.remove(index);
+                *selection = None;
+            }),
+            None => editor.edit(|arrows, selection| {
+                arrows.push(arrow);
+                *selection = Some(arrows.len() - 1);
+            }),
+        }
+    }
+}
+
+fn save_chart(
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Option<ResMut<Editor>>,
+    mut player: ResMut<SongPlayer>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let mut editor = match editor {
+        Some(editor) => editor,
+        None => return,
+    };
+
+    let ctrl =
+        keyboard_input.pressed(KeyCode::LControl) || keyboard_input.pressed(KeyCode::RControl);
+    let exit = keyboard_input.just_pressed(KeyCode::Return);
+    let save = exit || (ctrl && keyboard_input.just_pressed(KeyCode::S));
+    if !save {
+        return;
+    }
+
+    if editor.modified {
+        match editor.save() {
+            Ok(()) => info!("Saved map to assets/songs/{}", editor.path),
+            Err(err) => {
+                // Stay in the editor so the changes aren't lost
+                error!("Couldn't save map: {}", err);
+                return;
+            }
+        }
+    }
+
+    if exit {
+        player.stop();
+        state
+            .set(AppState::Menu)
+            .expect("Couldn't switch state to Menu");
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_timeline(
+    mut commands: Commands,
+    editor: Option<Res<Editor>>,
+    clock: Res<SongClock>,
+    windows: Res<Windows>,
+    arrow_materials: Res<ArrowMaterialResource>,
+    materials: Res<EditorMaterials>,
+    query: Query<Entity, With<TimelineEntity>>,
+    mut last_secs: Local<Option<f64>>,
+) {
+    let editor = match editor {
+        Some(editor) => editor,
+        None => return,
+    };
+
+    let secs = clock.seconds();
+    if !editor.is_changed() && *last_secs == Some(secs) {
+        return;
+    }
+    *last_secs = Some(secs);
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let half_width = windows
+        .get_primary()
+        .map_or(-SPAWN_POSITION, |window| window.width() / 2.);
+    let x_of = |time: f64| EDITOR_CURSOR_X + ((time - secs) as f32) * EDITOR_ZOOM;
+    let first_time = secs + ((-half_width - EDITOR_CURSOR_X) / EDITOR_ZOOM) as f64;
+    let last_time = secs + ((half_width - EDITOR_CURSOR_X) / EDITOR_ZOOM) as f64;
+    let lanes_height = Directions::Up.y() - Directions::Right.y() + EDITOR_ARROW_SIZE;
+
+    let mut spawn_line = |x: f32, width: f32, material: Handle<ColorMaterial>| {
+        commands
+            .spawn_bundle(SpriteBundle {
+                material,
+                sprite: Sprite::new(Vec2::new(width, lanes_height)),
+                transform: Transform::from_translation(Vec3::new(x, 0., 0.5)),
+                ..Default::default()
+            })
+            .insert(TimelineEntity);
+    };
+
+    // Beat grid, from the first visible line to the last one
+    let tempo = editor.tempo();
+    let division = editor.snap_division() as f64;
+    let first_line = (tempo.beat(first_time.max(0.)) * division).ceil() as i64;
+    let last_line = (tempo.beat(last_time) * division).floor() as i64;
+    for line in first_line..=last_line {
+        let beat = line as f64 / division;
+        let (width, material) = if tempo.is_measure_start(beat) {
+            (3., materials.measure_line.clone())
+        } else if line % division as i64 == 0 {
+            (2., materials.beat_line.clone())
+        } else {
+            (1., materials.snap_line.clone())
+        };
+        spawn_line(x_of(tempo.seconds(beat)), width, material);
+    }
+    spawn_line(EDITOR_CURSOR_X, 2., materials.cursor.clone());
+
+    use Directions::*;
+    for direction in [Up, Down, Left, Right].iter() {
+        let mut transform =
+            Transform::from_translation(Vec3::new(EDITOR_CURSOR_X, direction.y(), 0.8));
+        transform.rotate(Quat::from_rotation_z(direction.rotation()));
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: arrow_materials.border_material(),
+                sprite: Sprite::new(Vec2::new(EDITOR_ARROW_SIZE, EDITOR_ARROW_SIZE)),
+                transform,
+                ..Default::default()
+            })
+            .insert(TimelineEntity);
+    }
+
+    let margin = (EDITOR_ARROW_SIZE / EDITOR_ZOOM) as f64;
+    for (index, arrow) in editor.arrows().iter().enumerate() {
+        let click_time = arrow.seconds(&tempo);
+        let end_time = click_time + arrow.duration.unwrap_or(0.);
+        if end_time < first_time - margin || click_time > last_time + margin {
+            continue;
+        }
+
+        let position = Vec3::new(x_of(click_time), arrow.direction.y(), 1.);
+        if let Some(duration) = arrow.duration {
+            let length = duration as f32 * EDITOR_ZOOM;
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: arrow_materials.tail_material(arrow.speed),
+                    sprite: Sprite::new(Vec2::new(length, HOLD_TAIL_WIDTH)),
+                    transform: Transform::from_translation(
+                        position + Vec3::new(length / 2., 0., -0.2),
+                    ),
+                    ..Default::default()
+                })
+                .insert(TimelineEntity);
+        }
+
+        if editor.selected == Some(index) {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: materials.selection.clone(),
+                    sprite: Sprite::new(Vec2::new(EDITOR_ARROW_SIZE, EDITOR_ARROW_SIZE)),
+                    transform: Transform::from_translation(position - Vec3::new(0., 0., 0.1)),
+                    ..Default::default()
+                })
+                .insert(TimelineEntity);
+        }
+
+        let mut transform = Transform::from_translation(position);
+        transform.rotate(Quat::from_rotation_z(arrow.direction.rotation()));
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: arrow_materials.speed_material(arrow.speed),
+                sprite: Sprite::new(Vec2::new(EDITOR_ARROW_SIZE, EDITOR_ARROW_SIZE)),
+                transform,
+                ..Default::default()
+            })
+            .insert(TimelineEntity);
+    }
+}
+
+fn update_editor_text(
+    editor: Option<Res<Editor>>,
+    clock: Res<SongClock>,
+    mut query: Query<&mut Text, With<EditorText>>,
+) {
+    let editor = match editor {
+        Some(editor) => editor,
+        None => return,
+    };
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = EditorText::get_text(&editor, clock.seconds());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_editor(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<EditorUI>, With<TimelineEntity>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Editor>();
+    commands.remove_resource::<EditMap>();
+}
@@ -0,0 +1,174 @@
+use crate::{
+    consts::*,
+    results::LastScore,
+    score::ScoreResource,
+    types::{Difficulty, SongConfig},
+};
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct HighScoresPlugin;
+impl Plugin for HighScoresPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(HighScores::load()).add_system_set(
+            SystemSet::on_enter(AppState::Results).with_system(record_high_score.system()),
+        );
+    }
+}
+
+/// Best results for a chart
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HighScore {
+    pub score: usize,
+    pub accuracy: f32,
+    pub max_combo: usize,
+    /// Grade of the play with the best accuracy
+    pub grade: String,
+    /// Date of the last improvement, as `YYYY-MM-DD`
+    pub date: String,
+}
+
+/// High scores of every chart played, saved in [HIGH_SCORES_PATH]
+#[derive(Default)]
+pub struct HighScores {
+    /// Keyed by [score_key], so an edited chart starts over
+    scores: BTreeMap<String, HighScore>,
+}
+
+impl HighScores {
+    fn load() -> Self {
+        let contents = match fs::read_to_string(HIGH_SCORES_PATH) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match toml::from_str(&contents) {
+            Ok(scores) => Self { scores },
+            Err(err) => {
+                warn!("Couldn't parse {}, ignoring it: {}", HIGH_SCORES_PATH, err);
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let result = toml::to_string(&self.scores)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = Path::new(HIGH_SCORES_PATH).parent() {
+                    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+                }
+                fs::write(HIGH_SCORES_PATH, contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            error!("Couldn't save high scores: {}", err);
+        }
+    }
+
+    /// High score of the `difficulty` chart of the song file with the [chart_hash] `hash`
+    pub fn get(&self, hash: &str, difficulty: Difficulty) -> Option<&HighScore> {
+        self.scores.get(&score_key(hash, difficulty))
+    }
+
+    /// Keeps the best of `score` and the current high score of the chart, returns whether it improved
+    pub fn record(&mut self, path: &str, difficulty: Difficulty, score: &ScoreResource) -> bool {
+        let key = match chart_hash(path) {
+            Some(hash) => score_key(&hash, difficulty),
+            None => return false,
+        };
+
+        let new = HighScore {
+            score: score.score(),
+            accuracy: score.accuracy(),
+            max_combo: score.max_combo(),
+            grade: score.grade().name().to_string(),
+            date: today(),
+        };
+
+        let best = match self.scores.get(&key) {
+            None => new,
+            Some(old) => {
+                if new.score <= old.score
+                    && new.accuracy <= old.accuracy
+                    && new.max_combo <= old.max_combo
+                {
+                    return false;
+                }
+                let best_accuracy = if new.accuracy > old.accuracy {
+                    &new
+                } else {
+                    old
+                };
+                HighScore {
+                    score: new.score.max(old.score),
+                    accuracy: best_accuracy.accuracy,
+                    max_combo: new.max_combo.max(old.max_combo),
+                    grade: best_accuracy.grade.clone(),
+                    date: new.date.clone(),
+                }
+            }
+        };
+
+        self.scores.insert(key, best);
+        true
+    }
+}
+
+/// [chart_hash] of the song file, followed by the difficulty
+fn score_key(hash: &str, difficulty: Difficulty) -> String {
+    format!("{}-{:?}", hash, difficulty)
+}
+
+/// FNV-1a hash of the chart file in `assets/songs/{path}`
+pub fn chart_hash(path: &str) -> Option<String> {
+    let contents = fs::read(format!("assets/songs/{}", path)).ok()?;
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in contents {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Some(format!("{:016x}", hash))
+}
+
+/// Current UTC date as `YYYY-MM-DD`
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    // Days since the epoch to a civil date, from Howard Hinnant's `civil_from_days`
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn record_high_score(
+    mut high_scores: ResMut<HighScores>,
+    last_score: Res<LastScore>,
+    song_config: Res<SongConfig>,
+) {
+    if high_scores.record(&song_config.path, song_config.difficulty, &last_score.0) {
+        high_scores.save();
+    }
+}
@@ -0,0 +1,114 @@
+use crate::types::{Directions, SongConfigToml, Speed};
+use std::{fmt, fs, io, path::Path};
+
+pub mod osu;
+pub mod stepmania;
+
+/// Extensions of the chart files that can be imported
+pub const IMPORT_EXTENSIONS: [&str; 3] = ["sm", "ssc", "osu"];
+
+/// Speed given to imported arrows, the other formats have no equivalent
+const IMPORT_SPEED: Speed = Speed::Medium;
+
+/// Directions of the four columns of the imported charts, from left to right
+const COLUMNS: [Directions; 4] = [
+    Directions::Left,
+    Directions::Down,
+    Directions::Up,
+    Directions::Right,
+];
+
+/// Why a chart couldn't be imported
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    /// The file extension isn't one of [IMPORT_EXTENSIONS]
+    UnsupportedFormat(String),
+    /// A tag needed to build the chart is missing
+    MissingTag(&'static str),
+    /// A tag has a value that can't be converted
+    Invalid {
+        tag: String,
+        message: String,
+    },
+    /// The file has no chart that can be played with four arrows
+    NoChart,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "couldn't read file: {}", err),
+            ImportError::UnsupportedFormat(extension) => {
+                write!(f, "can't import .{} files", extension)
+            }
+            ImportError::MissingTag(tag) => write!(f, "missing `{}`", tag),
+            ImportError::Invalid { tag, message } => write!(f, "`{}`: {}", tag, message),
+            ImportError::NoChart => write!(f, "no chart with 4 columns found"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+/// Checks if the file at `path` can be imported, from its extension
+pub fn is_importable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMPORT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Reads the chart at `path`, picking the importer from its extension
+pub fn import_file(path: &Path) -> Result<SongConfigToml, ImportError> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    if !IMPORT_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(ImportError::UnsupportedFormat(extension));
+    }
+
+    // Charts are usually UTF-8, but older ones may have stray Latin-1 characters in the titles
+    let contents = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    match extension.as_str() {
+        "osu" => osu::import(&contents),
+        _ => stepmania::import(&contents),
+    }
+}
+
+/// `convert <chart> [output]` command, writes the chart in our format next to it by default.
+/// Returns the path written to
+pub fn convert_command(args: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    let input = match args {
+        [input] | [input, _] => Path::new(input),
+        _ => {
+            return Err(
+                "usage: bevy_rhythm convert <chart.sm|chart.ssc|chart.osu> [output.toml]".into(),
+            )
+        }
+    };
+    let output = match args.get(1) {
+        Some(output) => output.into(),
+        None => input.with_extension("toml"),
+    };
+
+    let config = import_file(input)
+        .map_err(|err| format!("couldn't import {}: {}", input.display(), err))?;
+    fs::write(&output, config.to_toml())?;
+
+    Ok(output.display().to_string())
+}
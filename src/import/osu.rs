@@ -0,0 +1,360 @@
+use super::{ImportError, COLUMNS, IMPORT_SPEED};
+use crate::types::{
+    ArrowTimeToml, BpmChange, ChartToml, Difficulty, SongConfigToml, SongMetadata, TimeSignature,
+};
+
+/// Game mode number of osu!mania
+const MANIA_MODE: &str = "3";
+
+/// Width of the playfield, the x position of hit objects tells their column
+const PLAYFIELD_WIDTH: f64 = 512.;
+
+/// Hit object type bit of osu!mania holds
+const HOLD_TYPE: u32 = 128;
+
+/// Imports an osu!mania `.osu` beatmap with 4 keys
+pub fn import(contents: &str) -> Result<SongConfigToml, ImportError> {
+    let sections = parse_sections(contents);
+    let key_value = |section: &str, key: &str| {
+        sections
+            .iter()
+            .filter(|(name, _)| name == section)
+            .flat_map(|(_, lines)| lines)
+            .filter_map(|line| line.split_once(':'))
+            .find(|(line_key, _)| line_key.trim() == key)
+            .map(|(_, value)| value.trim())
+    };
+
+    let mode = key_value("General", "Mode").unwrap_or("0");
+    if mode != MANIA_MODE {
+        return Err(ImportError::Invalid {
+            tag: "Mode".to_string(),
+            message: format!("mode {} isn't osu!mania", mode),
+        });
+    }
+    // The key count of mania beatmaps is kept in the circle size
+    if key_value("Difficulty", "CircleSize") != Some("4") {
+        return Err(ImportError::NoChart);
+    }
+
+    let title = key_value("Metadata", "Title")
+        .filter(|title| !title.is_empty())
+        .ok_or(ImportError::MissingTag("Title"))?;
+    let version = key_value("Metadata", "Version").unwrap_or_default();
+    let name = if version.is_empty() {
+        title.to_string()
+    } else {
+        format!("{} [{}]", title, version)
+    };
+    // Each difficulty of a beatmap set is its own file, the rating is the accuracy difficulty
+    let rating = key_value("Difficulty", "OverallDifficulty")
+        .and_then(|rating| rating.parse::<f64>().ok())
+        .filter(|rating| rating.is_finite())
+        .map_or(0, |rating| rating.round().max(0.) as u32);
+    let filename = key_value("General", "AudioFilename")
+        .filter(|filename| !filename.is_empty())
+        .ok_or(ImportError::MissingTag("AudioFilename"))?
+        .to_string();
+
+    let text = |section: &str, key: &str| {
+        key_value(section, key)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.to_string())
+    };
+    let metadata = SongMetadata {
+        artist: text("Metadata", "Artist"),
+        charter: text("Metadata", "Creator"),
+        genre: None,
+        length: None,
+        // -1 when the beatmap has no preview point
+        preview_start: key_value("General", "PreviewTime")
+            .and_then(|time| time.parse::<f64>().ok())
+            .filter(|time| time.is_finite() && *time >= 0.)
+            .map(|time| time / 1000.),
+        preview_end: None,
+        banner: None,
+        background: parse_background(&sections),
+    };
+
+    let mut arrows = Vec::new();
+    for line in section_lines(&sections, "HitObjects") {
+        arrows.push(parse_hit_object(line)?);
+    }
+    // Our charts can't have arrows before the start of the song
+    arrows.retain(|arrow| arrow.click_time >= 0.);
+    arrows.sort_by(|a, b| a.click_time.partial_cmp(&b.click_time).unwrap());
+
+    let tempo = parse_timing_points(&sections)?;
+    Ok(SongConfigToml {
+        name,
+        metadata,
+        filename,
+        offset: tempo.offset,
+        bpm: tempo.bpm,
+        bpm_changes: tempo.bpm_changes,
+        time_signatures: tempo.time_signatures,
+        arrows: Vec::new(),
+        charts: vec![ChartToml {
+            difficulty: guess_difficulty(version),
+            rating,
+            arrows,
+        }],
+    })
+}
+
+/// Difficulty from the usual names of beatmap versions, Normal if it has none of them
+fn guess_difficulty(version: &str) -> Difficulty {
+    let version = version.to_lowercase();
+    let has = |names: &[&str]| names.iter().any(|name| version.contains(name));
+
+    if has(&["beginner"]) {
+        Difficulty::Beginner
+    } else if has(&["easy"]) {
+        Difficulty::Easy
+    } else if has(&["insane", "expert", "extra", "another", "extreme"]) {
+        Difficulty::Expert
+    } else if has(&["hard", "hyper"]) {
+        Difficulty::Hard
+    } else {
+        Difficulty::Normal
+    }
+}
+
+/// Lines of each `[Section]` of a beatmap, without blank lines and comments
+fn parse_sections(contents: &str) -> Vec<(String, Vec<&str>)> {
+    let mut sections: Vec<(String, Vec<&str>)> = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            sections.push((name.to_string(), Vec::new()));
+        } else if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line);
+        }
+    }
+
+    sections
+}
+
+fn section_lines<'a>(
+    sections: &'a [(String, Vec<&'a str>)],
+    section: &'a str,
+) -> impl Iterator<Item = &'a str> {
+    sections
+        .iter()
+        .filter(move |(name, _)| name == section)
+        .flat_map(|(_, lines)| lines.iter().copied())
+}
+
+/// Image of the `0,0,"filename",x,y` background event
+fn parse_background(sections: &[(String, Vec<&str>)]) -> Option<String> {
+    section_lines(sections, "Events").find_map(|line| {
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+        match fields[..] {
+            ["0", "0", filename, ..] => Some(filename.trim_matches('"').to_string()),
+            _ => None,
+        }
+    })
+}
+
+fn invalid(section: &str, line: &str) -> ImportError {
+    ImportError::Invalid {
+        tag: section.to_string(),
+        message: format!("can't read `{}`", line),
+    }
+}
+
+/// Tempo map of a beatmap, from its uninherited timing points: the ones that set the beat length
+#[derive(Default)]
+struct Tempo {
+    offset: f64,
+    bpm: Option<f64>,
+    bpm_changes: Vec<BpmChange>,
+    time_signatures: Vec<TimeSignature>,
+}
+
+fn parse_timing_points(sections: &[(String, Vec<&str>)]) -> Result<Tempo, ImportError> {
+    let mut tempo = Tempo::default();
+    // Time in milliseconds, beat, beat length in milliseconds and meter of the last point
+    let mut last: Option<(f64, f64, f64, u32)> = None;
+
+    for line in section_lines(sections, "TimingPoints") {
+        // time,beatLength,meter,sampleSet,sampleIndex,volume,uninherited,effects
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+        let number = |index: usize| {
+            fields
+                .get(index)
+                .and_then(|field| field.parse::<f64>().ok())
+                .filter(|number| number.is_finite())
+        };
+        let time = number(0).ok_or_else(|| invalid("TimingPoints", line))?;
+        let beat_length = number(1).ok_or_else(|| invalid("TimingPoints", line))?;
+        let meter = number(2).map_or(4, |meter| meter as u32).max(1);
+        // Old beatmaps don't have the uninherited field, inherited points have negative lengths
+        let uninherited = fields.get(6).is_none_or(|field| *field == "1");
+        if !uninherited || beat_length <= 0. {
+            continue;
+        }
+
+        let beat = match last {
+            None => {
+                tempo.offset = time / 1000.;
+                tempo.bpm = Some(60000. / beat_length);
+                0.
+            }
+            Some((last_time, last_beat, last_length, _)) => {
+                let beat = last_beat + (time - last_time) / last_length;
+                tempo.bpm_changes.push(BpmChange {
+                    beat,
+                    bpm: 60000. / beat_length,
+                });
+                beat
+            }
+        };
+        if last.map_or(meter != 4, |(_, _, _, last_meter)| last_meter != meter) {
+            tempo.time_signatures.push(TimeSignature {
+                beat,
+                numerator: meter,
+                denominator: 4,
+            });
+        }
+
+        last = Some((time, beat, beat_length, meter));
+    }
+
+    Ok(tempo)
+}
+
+/// Converts a `x,y,time,type,hitSound,objectParams,hitSample` hit object into an arrow
+fn parse_hit_object(line: &str) -> Result<ArrowTimeToml, ImportError> {
+    let fields: Vec<_> = line.split(',').map(str::trim).collect();
+    let number = |index: usize| -> Result<f64, ImportError> {
+        fields
+            .get(index)
+            .and_then(|field| field.parse::<f64>().ok())
+            .filter(|number| number.is_finite())
+            .ok_or_else(|| invalid("HitObjects", line))
+    };
+
+    let x = number(0)?;
+    let time = number(2)?;
+    let object_type = number(3)? as u32;
+
+    let column =
+        ((x * COLUMNS.len() as f64 / PLAYFIELD_WIDTH).floor() as usize).min(COLUMNS.len() - 1);
+
+    // Holds start their params with `endTime:`
+    let duration = if object_type & HOLD_TYPE != 0 {
+        let end_time: f64 = fields
+            .get(5)
+            .and_then(|params| params.split(':').next())
+            .and_then(|end_time| end_time.parse().ok())
+            .ok_or_else(|| invalid("HitObjects", line))?;
+        Some((end_time - time) / 1000.).filter(|duration| *duration > 0.)
+    } else {
+        None
+    };
+
+    Ok(ArrowTimeToml {
+        click_time: time / 1000.,
+        beat: None,
+        speed: IMPORT_SPEED,
+        direction: COLUMNS[column],
+        duration,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Directions;
+
+    const BEATMAP: &str = "osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+PreviewTime: 2500
+Mode: 3
+
+[Metadata]
+Title:Test song
+Artist:Someone
+Creator:Mapper
+Version:Hard
+
+[Difficulty]
+CircleSize:4
+OverallDifficulty:7.6
+
+[TimingPoints]
+1000,500,4,2,0,100,1,0
+2000,-50,4,2,0,100,0,0
+3000,1000,3,2,0,100,1,0
+
+[HitObjects]
+64,192,1000,1,0,0:0:0:0:
+192,192,1500,1,0,0:0:0:0:
+320,192,2000,128,0,3000:0:0:0:0:
+448,192,2500,1,0,0:0:0:0:
+";
+
+    #[test]
+    fn imports_song_and_tempo() {
+        let config = import(BEATMAP).unwrap();
+        assert_eq!(config.name, "Test song [Hard]");
+        assert_eq!(config.filename, "audio.mp3");
+        assert_eq!(config.metadata.charter.as_deref(), Some("Mapper"));
+        assert_eq!(config.metadata.preview_start, Some(2.5));
+        assert_eq!(config.offset, 1.);
+        assert_eq!(config.bpm, Some(120.));
+        // The inherited point doesn't change the tempo
+        assert_eq!(config.bpm_changes.len(), 1);
+        assert_eq!(
+            (config.bpm_changes[0].beat, config.bpm_changes[0].bpm),
+            (4., 60.)
+        );
+        assert_eq!(config.time_signatures.len(), 1);
+        assert_eq!(config.time_signatures[0].numerator, 3);
+    }
+
+    #[test]
+    fn imports_columns_and_holds() {
+        let config = import(BEATMAP).unwrap();
+        let chart = &config.charts[0];
+        assert_eq!(chart.difficulty, Difficulty::Hard);
+        assert_eq!(chart.rating, 8);
+
+        let arrows: Vec<_> = chart
+            .arrows
+            .iter()
+            .map(|arrow| (arrow.click_time, arrow.direction, arrow.duration))
+            .collect();
+        assert_eq!(
+            arrows,
+            [
+                (1., Directions::Left, None),
+                (1.5, Directions::Down, None),
+                (2., Directions::Up, Some(1.)),
+                (2.5, Directions::Right, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_modes_and_key_counts() {
+        let standard = BEATMAP.replace("Mode: 3", "Mode: 0");
+        assert!(matches!(
+            import(&standard),
+            Err(ImportError::Invalid { tag, .. }) if tag == "Mode"
+        ));
+
+        let seven_keys = BEATMAP.replace("CircleSize:4", "CircleSize:7");
+        assert!(matches!(import(&seven_keys), Err(ImportError::NoChart)));
+    }
+}
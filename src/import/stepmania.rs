@@ -0,0 +1,435 @@
+use super::{ImportError, COLUMNS, IMPORT_SPEED};
+use crate::types::{ArrowTimeToml, BpmChange, ChartToml, Difficulty, SongConfigToml, SongMetadata};
+
+/// Imports a StepMania `.sm` or `.ssc` simfile, using the first `dance-single` chart of each difficulty
+pub fn import(contents: &str) -> Result<SongConfigToml, ImportError> {
+    let tags = parse_tags(contents);
+    let song_tag = |name: &str| {
+        tags.iter()
+            .take_while(|(tag, _)| tag != "NOTEDATA")
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let name = song_tag("TITLE")
+        .filter(|title| !title.is_empty())
+        .ok_or(ImportError::MissingTag("#TITLE"))?
+        .to_string();
+    let filename = song_tag("MUSIC")
+        .filter(|music| !music.is_empty())
+        .ok_or(ImportError::MissingTag("#MUSIC"))?
+        .to_string();
+    let text_tag = |name: &str| {
+        song_tag(name)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.to_string())
+    };
+    let number_tag = |name: &str| {
+        song_tag(name)
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value >= 0.)
+    };
+    let preview_start = number_tag("SAMPLESTART");
+    let metadata = SongMetadata {
+        artist: text_tag("ARTIST"),
+        charter: text_tag("CREDIT"),
+        genre: text_tag("GENRE"),
+        length: None,
+        preview_start,
+        preview_end: number_tag("SAMPLELENGTH")
+            .filter(|length| *length > 0.)
+            .map(|length| preview_start.unwrap_or(0.) + length),
+        banner: text_tag("BANNER"),
+        background: text_tag("BACKGROUND"),
+    };
+    let song_timing = TimingTags {
+        offset: song_tag("OFFSET"),
+        bpms: song_tag("BPMS"),
+        stops: song_tag("STOPS"),
+    };
+
+    let mut charts: Vec<ChartToml> = Vec::new();
+    let mut first_timing = None;
+    for chart in parse_charts(&tags)
+        .into_iter()
+        .filter(|chart| chart.steps_type == "dance-single")
+    {
+        let difficulty = parse_difficulty(chart.difficulty);
+        if charts.iter().any(|other| other.difficulty == difficulty) {
+            continue;
+        }
+
+        // `.ssc` charts can have their own timing
+        let timing = TimingData::parse(&TimingTags {
+            offset: chart.timing.offset.or(song_timing.offset),
+            bpms: chart.timing.bpms.or(song_timing.bpms),
+            stops: chart.timing.stops.or(song_timing.stops),
+        })?;
+
+        let mut arrows = parse_notes(chart.notes, &timing)?;
+        // Our charts can't have arrows before the start of the song
+        arrows.retain(|arrow| arrow.click_time >= 0.);
+        arrows.sort_by(|a, b| a.click_time.partial_cmp(&b.click_time).unwrap());
+
+        charts.push(ChartToml {
+            difficulty,
+            rating: chart.meter.parse().unwrap_or(0),
+            arrows,
+        });
+        first_timing.get_or_insert(timing);
+    }
+    let timing = first_timing.ok_or(ImportError::NoChart)?;
+
+    // Arrows are already in seconds, the tempo map only draws the beat grid of the editor.
+    // It comes from the first chart and doesn't have stops, so the grid drifts after the first one
+    Ok(SongConfigToml {
+        name,
+        metadata,
+        filename,
+        offset: -timing.offset,
+        bpm: Some(timing.bpms[0].1),
+        bpm_changes: timing.bpms[1..]
+            .iter()
+            .map(|(beat, bpm)| BpmChange {
+                beat: *beat,
+                bpm: *bpm,
+            })
+            .collect(),
+        time_signatures: Vec::new(),
+        arrows: Vec::new(),
+        charts,
+    })
+}
+
+/// Our difficulty closest to a StepMania one, edits count as the hardest
+fn parse_difficulty(difficulty: &str) -> Difficulty {
+    match difficulty.to_lowercase().as_str() {
+        "beginner" => Difficulty::Beginner,
+        "easy" => Difficulty::Easy,
+        "hard" => Difficulty::Hard,
+        "challenge" | "edit" => Difficulty::Expert,
+        _ => Difficulty::Normal,
+    }
+}
+
+/// `#TAG:value;` pairs of a simfile in order, with comments removed. Tags are uppercased
+fn parse_tags(contents: &str) -> Vec<(String, String)> {
+    let stripped: String = contents
+        .lines()
+        .map(|line| line.find("//").map_or(line, |comment| &line[..comment]))
+        .flat_map(|line| line.chars().chain(Some('\n')))
+        .collect();
+
+    let mut tags = Vec::new();
+    let mut rest = stripped.as_str();
+    while let Some(start) = rest.find('#') {
+        rest = &rest[start + 1..];
+        let colon = match rest.find(':') {
+            Some(colon) => colon,
+            None => break,
+        };
+        let tag = rest[..colon].trim().to_uppercase();
+        rest = &rest[colon + 1..];
+
+        let end = rest.find(';').unwrap_or(rest.len());
+        tags.push((tag, rest[..end].trim().to_string()));
+        rest = &rest[(end + 1).min(rest.len())..];
+    }
+    tags
+}
+
+/// Raw values of the tags that time a chart
+#[derive(Default, Clone, Copy)]
+struct TimingTags<'a> {
+    offset: Option<&'a str>,
+    bpms: Option<&'a str>,
+    stops: Option<&'a str>,
+}
+
+#[derive(Default)]
+struct ChartTags<'a> {
+    steps_type: &'a str,
+    difficulty: &'a str,
+    meter: &'a str,
+    notes: &'a str,
+    timing: TimingTags<'a>,
+}
+
+/// Charts of a simfile, from the `#NOTES` tags of `.sm` files or the `#NOTEDATA` sections of `.ssc` files
+fn parse_charts(tags: &[(String, String)]) -> Vec<ChartTags<'_>> {
+    let mut charts = Vec::new();
+    let mut ssc_chart: Option<ChartTags> = None;
+
+    for (tag, value) in tags {
+        let value = value.as_str();
+        match (tag.as_str(), ssc_chart.as_mut()) {
+            ("NOTEDATA", _) => {
+                charts.extend(ssc_chart.take());
+                ssc_chart = Some(ChartTags::default());
+            }
+            ("STEPSTYPE", Some(chart)) => chart.steps_type = value,
+            ("DIFFICULTY", Some(chart)) => chart.difficulty = value,
+            ("METER", Some(chart)) => chart.meter = value,
+            ("NOTES", Some(chart)) => chart.notes = value,
+            ("OFFSET", Some(chart)) => chart.timing.offset = Some(value),
+            ("BPMS", Some(chart)) => chart.timing.bpms = Some(value),
+            ("STOPS", Some(chart)) => chart.timing.stops = Some(value),
+            // `.sm` charts: type, description, difficulty, meter, radar values and notes
+            ("NOTES", None) => {
+                let fields: Vec<_> = value.splitn(6, ':').map(str::trim).collect();
+                if let [steps_type, _, difficulty, meter, _, notes] = fields[..] {
+                    charts.push(ChartTags {
+                        steps_type,
+                        difficulty,
+                        meter,
+                        notes,
+                        ..Default::default()
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    charts.extend(ssc_chart);
+
+    charts
+}
+
+/// Converts beats to seconds
+struct TimingData {
+    /// Seconds between the start of the audio and beat 0, negated
+    offset: f64,
+    /// Beat at which each tempo starts, and its beats per minute
+    bpms: Vec<(f64, f64)>,
+    /// Beat at which the chart stops scrolling, and for how many seconds
+    stops: Vec<(f64, f64)>,
+}
+
+impl TimingData {
+    fn parse(tags: &TimingTags) -> Result<Self, ImportError> {
+        let offset = match tags.offset {
+            Some(offset) if !offset.is_empty() => {
+                offset.parse().map_err(|_| ImportError::Invalid {
+                    tag: "#OFFSET".to_string(),
+                    message: format!("`{}` is not a number", offset),
+                })?
+            }
+            _ => 0.,
+        };
+
+        let mut bpms = parse_pairs("#BPMS", tags.bpms.ok_or(ImportError::MissingTag("#BPMS"))?)?;
+        if bpms.is_empty() {
+            return Err(ImportError::MissingTag("#BPMS"));
+        }
+        if let Some((_, bpm)) = bpms.iter().find(|(_, bpm)| *bpm <= 0.) {
+            return Err(ImportError::Invalid {
+                tag: "#BPMS".to_string(),
+                message: format!("tempo {} is not supported", bpm),
+            });
+        }
+        bpms.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        // The first tempo counts from the start of the chart
+        bpms[0].0 = 0.;
+
+        let stops = parse_pairs("#STOPS", tags.stops.unwrap_or_default())?;
+
+        Ok(Self {
+            offset,
+            bpms,
+            stops,
+        })
+    }
+
+    /// Seconds since the start of the audio at `beat`
+    fn seconds(&self, beat: f64) -> f64 {
+        let mut secs = -self.offset;
+
+        for (i, (start, bpm)) in self.bpms.iter().enumerate() {
+            if *start >= beat {
+                break;
+            }
+            let end = self
+                .bpms
+                .get(i + 1)
+                .map_or(beat, |(next, _)| next.min(beat));
+            secs += (end - start) * 60. / bpm;
+        }
+
+        // A stop happens after the notes on its beat
+        secs + self
+            .stops
+            .iter()
+            .filter(|(stop, _)| *stop < beat)
+            .map(|(_, duration)| duration)
+            .sum::<f64>()
+    }
+}
+
+/// Parses `beat=value` pairs of numbers separated by commas
+fn parse_pairs(tag: &str, value: &str) -> Result<Vec<(f64, f64)>, ImportError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let parsed = pair
+                .split_once('=')
+                .and_then(|(beat, value)| {
+                    Some((beat.trim().parse().ok()?, value.trim().parse().ok()?))
+                })
+                .filter(|(beat, value): &(f64, f64)| beat.is_finite() && value.is_finite());
+            parsed.ok_or_else(|| ImportError::Invalid {
+                tag: tag.to_string(),
+                message: format!("`{}` is not a `beat=value` pair", pair),
+            })
+        })
+        .collect()
+}
+
+/// Arrows of the note data of a chart: measures separated by commas, each with rows of 4 columns
+fn parse_notes(notes: &str, timing: &TimingData) -> Result<Vec<ArrowTimeToml>, ImportError> {
+    let mut arrows: Vec<ArrowTimeToml> = Vec::new();
+    // Index in `arrows` of the hold started in each column
+    let mut holds: [Option<usize>; 4] = [None; 4];
+
+    for (measure, rows) in notes.split(',').enumerate() {
+        let rows: Vec<_> = rows.split_whitespace().collect();
+
+        for (row_index, row) in rows.iter().enumerate() {
+            if row.chars().count() != COLUMNS.len() {
+                return Err(ImportError::Invalid {
+                    tag: "#NOTES".to_string(),
+                    message: format!(
+                        "row `{}` of measure {} doesn't have {} columns",
+                        row,
+                        measure,
+                        COLUMNS.len()
+                    ),
+                });
+            }
+
+            let beat = (measure as f64 + row_index as f64 / rows.len() as f64) * 4.;
+            let secs = timing.seconds(beat);
+
+            for (column, note) in row.chars().enumerate() {
+                match note {
+                    // Taps and lifts
+                    '1' | 'L' => {}
+                    // Hold and roll heads
+                    '2' | '4' => holds[column] = Some(arrows.len()),
+                    // Hold and roll tails
+                    '3' => {
+                        if let Some(head) = holds[column].take() {
+                            let duration = secs - arrows[head].click_time;
+                            arrows[head].duration =
+                                Some(duration).filter(|duration| *duration > 0.);
+                        }
+                        continue;
+                    }
+                    // Empty, mines, fakes and keysounds
+                    _ => continue,
+                }
+
+                arrows.push(ArrowTimeToml {
+                    click_time: secs,
+                    beat: None,
+                    speed: IMPORT_SPEED,
+                    direction: COLUMNS[column],
+                    duration: None,
+                });
+            }
+        }
+    }
+
+    Ok(arrows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Directions;
+
+    const SIMFILE: &str = "#TITLE:Test song;
+#ARTIST:Someone;
+#MUSIC:test.ogg;
+#OFFSET:-0.5;
+#BPMS:0=120,4=60;
+#NOTES:
+     dance-double:
+     :
+     Hard:
+     10:
+     0,0,0,0,0:
+00000000
+;
+#NOTES:
+     dance-single:
+     :
+     Hard:
+     9:
+     0,0,0,0,0:
+// first measure
+1000
+0000
+0100
+0000
+,
+2000
+0000
+3000
+0001
+;
+";
+
+    #[test]
+    fn imports_timing_and_song_tags() {
+        let config = import(SIMFILE).unwrap();
+        assert_eq!(config.name, "Test song");
+        assert_eq!(config.filename, "test.ogg");
+        assert_eq!(config.metadata.artist.as_deref(), Some("Someone"));
+        assert_eq!(config.offset, 0.5);
+        assert_eq!(config.bpm, Some(120.));
+        assert_eq!(config.bpm_changes.len(), 1);
+        assert_eq!(
+            (config.bpm_changes[0].beat, config.bpm_changes[0].bpm),
+            (4., 60.)
+        );
+    }
+
+    #[test]
+    fn imports_notes_and_holds() {
+        let config = import(SIMFILE).unwrap();
+        // The dance-double chart is skipped
+        assert_eq!(config.charts.len(), 1);
+        let chart = &config.charts[0];
+        assert_eq!(chart.difficulty, Difficulty::Hard);
+        assert_eq!(chart.rating, 9);
+
+        let arrows: Vec<_> = chart
+            .arrows
+            .iter()
+            .map(|arrow| (arrow.click_time, arrow.direction, arrow.duration))
+            .collect();
+        assert_eq!(
+            arrows,
+            [
+                (0.5, Directions::Left, None),
+                (1.5, Directions::Down, None),
+                (2.5, Directions::Left, Some(2.)),
+                (5.5, Directions::Right, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_single_charts() {
+        let contents = "#TITLE:Test song;\n#MUSIC:test.ogg;\n#BPMS:0=120;\n";
+        assert!(matches!(import(contents), Err(ImportError::NoChart)));
+
+        let contents = "#TITLE:Test song;\n#MUSIC:test.ogg;\n#NOTES:dance-single::Easy:1::1000;\n";
+        assert!(matches!(
+            import(contents),
+            Err(ImportError::MissingTag("#BPMS"))
+        ));
+    }
+}
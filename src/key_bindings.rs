@@ -0,0 +1,431 @@
+use crate::{
+    consts::*,
+    lane_input::{ConnectedPads, PadInput, PadPresses},
+    menu::{button_color_system, ButtonMaterials},
+    types::Directions,
+};
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Inputs bound to each lane, a lane can have any number of them
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(
+    default,
+    bound(deserialize = "T: serde::Deserialize<'de>, LaneBindings<T>: Default")
+)]
+pub struct LaneBindings<T> {
+    pub up: Vec<T>,
+    pub down: Vec<T>,
+    pub left: Vec<T>,
+    pub right: Vec<T>,
+}
+
+/// Keyboard keys of each lane
+pub type KeyBindings = LaneBindings<KeyCode>;
+
+/// Gamepad buttons and axes of each lane
+pub type PadProfile = LaneBindings<PadInput>;
+
+impl Default for KeyBindings {
+    /// Arrow keys and WASD
+    fn default() -> Self {
+        Self {
+            up: vec![KeyCode::Up, KeyCode::W],
+            down: vec![KeyCode::Down, KeyCode::S],
+            left: vec![KeyCode::Left, KeyCode::A],
+            right: vec![KeyCode::Right, KeyCode::D],
+        }
+    }
+}
+
+impl Default for PadProfile {
+    /// D-pad, as buttons or as axes, and the face buttons
+    fn default() -> Self {
+        use GamepadAxisType::*;
+        use GamepadButtonType::*;
+        use PadInput::*;
+        Self {
+            up: vec![Button(DPadUp), Button(North), Axis(DPadY, true)],
+            down: vec![Button(DPadDown), Button(South), Axis(DPadY, false)],
+            left: vec![Button(DPadLeft), Button(West), Axis(DPadX, false)],
+            right: vec![Button(DPadRight), Button(East), Axis(DPadX, true)],
+        }
+    }
+}
+
+impl<T: PartialEq> LaneBindings<T> {
+    /// Inputs bound to the lane of `direction`
+    pub fn keys(&self, direction: Directions) -> &[T] {
+        match direction {
+            Directions::Up => &self.up,
+            Directions::Down => &self.down,
+            Directions::Left => &self.left,
+            Directions::Right => &self.right,
+        }
+    }
+
+    fn keys_mut(&mut self, direction: Directions) -> &mut Vec<T> {
+        match direction {
+            Directions::Up => &mut self.up,
+            Directions::Down => &mut self.down,
+            Directions::Left => &mut self.left,
+            Directions::Right => &mut self.right,
+        }
+    }
+
+    /// Adds `key` to the lane of `direction`, taking it away from the other lanes
+    fn bind(&mut self, direction: Directions, key: T) {
+        for lane in LANES.iter() {
+            self.keys_mut(*lane).retain(|code| *code != key);
+        }
+        self.keys_mut(direction).push(key);
+    }
+}
+
+/// Binding profiles of the gamepads and dance mats
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct GamepadBindings {
+    /// Profile of the devices that don't have their own
+    pub default: PadProfile,
+    /// Profiles by device name
+    pub devices: BTreeMap<String, PadProfile>,
+}
+
+impl GamepadBindings {
+    pub fn profile(&self, device: &str) -> &PadProfile {
+        self.devices.get(device).unwrap_or(&self.default)
+    }
+
+    /// Profile of `device`, made from the default profile if it had none
+    fn profile_mut(&mut self, device: &str) -> &mut PadProfile {
+        let default = &self.default;
+        self.devices
+            .entry(device.to_string())
+            .or_insert_with(|| default.clone())
+    }
+}
+
+pub const LANES: [Directions; 4] = [
+    Directions::Up,
+    Directions::Down,
+    Directions::Left,
+    Directions::Right,
+];
+
+/// Screen to change the [KeyBindings], opened from the menu
+pub struct KeyBindingsPlugin;
+impl Plugin for KeyBindingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::KeyBindings).with_system(setup_key_bindings.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::KeyBindings)
+                .with_system(button_color_system.system())
+                .with_system(binding_button_system.system())
+                .with_system(pick_shown_pad.system())
+                .with_system(capture_key.system())
+                .with_system(update_lane_text.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::KeyBindings).with_system(despawn_key_bindings.system()),
+        );
+    }
+}
+
+struct KeyBindingsUI;
+
+/// Text of the button of a lane, listing its inputs
+struct LaneText(Directions);
+
+/// Text naming the gamepad whose profile is shown
+struct PadText;
+
+/// Lane waiting for an input, only present while waiting
+struct Capturing(Directions);
+
+/// Name of the gamepad whose profile is shown and changed, the default profile if `None`
+struct ShownPad(Option<String>);
+
+enum BindingButton {
+    Lane(Directions),
+    Reset,
+    Back,
+}
+
+const HELP_TEXT: &str =
+    "Click a lane, then press a key or a gamepad button to add it to the lane\n\
+    Backspace: clear the lane, Esc: cancel\n\
+    Press a button on a gamepad or dance mat to change its own profile";
+
+fn lane_text(
+    direction: Directions,
+    key_bindings: &KeyBindings,
+    profile: &PadProfile,
+    capturing: bool,
+) -> String {
+    if capturing {
+        return format!("{:?}: press a key or a button...", direction);
+    }
+
+    fn list<T: ToString>(inputs: &[T]) -> String {
+        if inputs.is_empty() {
+            return "none".to_string();
+        }
+        inputs
+            .iter()
+            .map(|input| input.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    let keys: Vec<_> = key_bindings
+        .keys(direction)
+        .iter()
+        .map(|key| format!("{:?}", key))
+        .collect();
+    format!(
+        "{:?}: {}\nGamepad: {}",
+        direction,
+        list(&keys),
+        list(profile.keys(direction))
+    )
+}
+
+fn pad_text(shown_pad: &ShownPad) -> String {
+    match &shown_pad.0 {
+        Some(name) => format!("Gamepad profile: {}", name),
+        None => "Gamepad profile: default".to_string(),
+    }
+}
+
+/// Profile of the shown gamepad, to be changed
+fn shown_profile_mut<'a>(
+    gamepad_bindings: &'a mut GamepadBindings,
+    shown_pad: &ShownPad,
+) -> &'a mut PadProfile {
+    match &shown_pad.0 {
+        Some(name) => gamepad_bindings.profile_mut(name),
+        None => &mut gamepad_bindings.default,
+    }
+}
+
+fn setup_key_bindings(
+    mut commands: Commands,
+    button_materials: Res<ButtonMaterials>,
+    key_bindings: Res<KeyBindings>,
+    gamepad_bindings: Res<GamepadBindings>,
+    pads: Res<ConnectedPads>,
+) {
+    let text_style = TextStyle {
+        font: button_materials.font.clone(),
+        font_size: 20.,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    // The first gamepad connected, by name, until another one is pressed
+    let shown_pad = ShownPad(pads.0.values().min().cloned());
+    let profile = match &shown_pad.0 {
+        Some(name) => gamepad_bindings.profile(name),
+        None => &gamepad_bindings.default,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: button_materials.none.clone(),
+            ..Default::default()
+        })
+        .insert(KeyBindingsUI)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(HELP_TEXT, text_style.clone(), Default::default()),
+                ..Default::default()
+            });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        pad_text(&shown_pad),
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(PadText);
+
+            let buttons = LANES
+                .iter()
+                .map(|direction| BindingButton::Lane(*direction))
+                .chain([BindingButton::Reset, BindingButton::Back]);
+            for button in buttons {
+                let (text, lane) = match button {
+                    BindingButton::Lane(direction) => (
+                        lane_text(direction, &key_bindings, profile, false),
+                        Some(direction),
+                    ),
+                    BindingButton::Reset => ("Reset to defaults".to_string(), None),
+                    BindingButton::Back => ("Back".to_string(), None),
+                };
+
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(450.0), Val::Auto),
+                            min_size: Size::new(Val::Auto, Val::Px(50.0)),
+                            margin: Rect::all(Val::Px(10.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        let mut text = parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(text, text_style.clone(), Default::default()),
+                            ..Default::default()
+                        });
+                        if let Some(direction) = lane {
+                            text.insert(LaneText(direction));
+                        }
+                    })
+                    .insert(button);
+            }
+        });
+
+    commands.insert_resource(shown_pad);
+}
+
+#[allow(clippy::type_complexity)]
+fn binding_button_system(
+    mut commands: Commands,
+    query: Query<(&Interaction, &BindingButton), (Changed<Interaction>, With<Button>)>,
+    shown_pad: Res<ShownPad>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut gamepad_bindings: ResMut<GamepadBindings>,
+    mut state: ResMut<State<AppState>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            BindingButton::Lane(direction) => commands.insert_resource(Capturing(*direction)),
+            BindingButton::Reset => {
+                *key_bindings = KeyBindings::default();
+                match &shown_pad.0 {
+                    Some(name) => {
+                        gamepad_bindings.devices.remove(name);
+                    }
+                    None => gamepad_bindings.default = PadProfile::default(),
+                }
+            }
+            BindingButton::Back => state
+                .set(AppState::Menu)
+                .expect("Couldn't switch state to Menu"),
+        }
+    }
+}
+
+/// Shows the profile of the last gamepad pressed
+fn pick_shown_pad(
+    presses: Res<PadPresses>,
+    pads: Res<ConnectedPads>,
+    mut shown_pad: ResMut<ShownPad>,
+) {
+    if let Some((gamepad, _)) = presses.just_pressed.last() {
+        let name = pads.0.get(gamepad).cloned();
+        if name.is_some() && shown_pad.0 != name {
+            shown_pad.0 = name;
+        }
+    }
+}
+
+/// Binds the next key or gamepad input pressed to the lane being captured
+#[allow(clippy::too_many_arguments)]
+fn capture_key(
+    mut commands: Commands,
+    capturing: Option<Res<Capturing>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    presses: Res<PadPresses>,
+    pads: Res<ConnectedPads>,
+    shown_pad: Res<ShownPad>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut gamepad_bindings: ResMut<GamepadBindings>,
+) {
+    let direction = match capturing {
+        Some(capturing) => capturing.0,
+        None => return,
+    };
+
+    if let Some((gamepad, input)) = presses.just_pressed.last() {
+        if let Some(name) = pads.0.get(gamepad) {
+            gamepad_bindings.profile_mut(name).bind(direction, *input);
+            commands.remove_resource::<Capturing>();
+        }
+        return;
+    }
+
+    let key = match keyboard_input.get_just_pressed().next() {
+        Some(key) => *key,
+        None => return,
+    };
+    match key {
+        KeyCode::Escape => {}
+        KeyCode::Back => {
+            key_bindings.keys_mut(direction).clear();
+            shown_profile_mut(&mut gamepad_bindings, &shown_pad)
+                .keys_mut(direction)
+                .clear();
+        }
+        key => key_bindings.bind(direction, key),
+    }
+    commands.remove_resource::<Capturing>();
+}
+
+#[allow(clippy::type_complexity)]
+fn update_lane_text(
+    key_bindings: Res<KeyBindings>,
+    gamepad_bindings: Res<GamepadBindings>,
+    shown_pad: Res<ShownPad>,
+    capturing: Option<Res<Capturing>>,
+    mut lane_query: Query<(&mut Text, &LaneText)>,
+    mut pad_query: Query<&mut Text, (With<PadText>, Without<LaneText>)>,
+) {
+    let profile = match &shown_pad.0 {
+        Some(name) => gamepad_bindings.profile(name),
+        None => &gamepad_bindings.default,
+    };
+    let capturing = capturing.map(|capturing| capturing.0);
+
+    // Only setting changed text, so it isn't laid out again every frame
+    for (mut text, lane) in lane_query.iter_mut() {
+        let value = lane_text(lane.0, &key_bindings, profile, capturing == Some(lane.0));
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+    for mut text in pad_query.iter_mut() {
+        let value = pad_text(&shown_pad);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn despawn_key_bindings(mut commands: Commands, query: Query<Entity, With<KeyBindingsUI>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Capturing>();
+    commands.remove_resource::<ShownPad>();
+}
@@ -0,0 +1,457 @@
+use crate::{
+    consts::*,
+    key_bindings::{GamepadBindings, KeyBindings, LANES},
+    types::Directions,
+};
+use bevy::{
+    input::{keyboard::KeyboardInput, ElementState, InputSystem},
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use gilrs::{EventType, Gilrs};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fmt,
+    time::{Instant, SystemTime},
+};
+
+/// Reads the lanes from the keyboard and gamepads into [LaneEvent]s, and into an `Input<Directions>`
+/// for what doesn't need their timing. Reads the gamepads itself rather than through bevy,
+/// which drops the time of their events. Keys have no time of their own, so they're only as
+/// precise as the frame rate
+pub struct LaneInputPlugin;
+impl Plugin for LaneInputPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Input<Directions>>()
+            .add_event::<LaneEvent>()
+            .init_resource::<ConnectedPads>()
+            .init_resource::<PadPresses>()
+            .init_resource::<PadEvents>()
+            .init_resource::<HeldInputs>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_lane_input.system().after(InputSystem),
+            );
+
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                app.insert_non_send_resource(gilrs)
+                    .add_system_to_stage(CoreStage::PreUpdate, read_gamepads.exclusive_system());
+            }
+            Err(err) => error!("Couldn't read gamepads: {}", err),
+        }
+    }
+}
+
+/// An input bound to a lane being pressed, or the lane being released once none of its inputs
+/// are held. Has when the input happened for gamepads, and when the frame read it for keys.
+/// Pressing another input of a held lane presses it again.
+/// Use [crate::clock::SongClock::seconds_at] for the song time
+pub struct LaneEvent {
+    pub direction: Directions,
+    pub pressed: bool,
+    pub time: Instant,
+}
+
+/// Gamepad button, or direction of a gamepad axis, that can be bound to a lane.
+/// Dance mats show up as gamepads, with their panels as buttons or axes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PadInput {
+    Button(GamepadButtonType),
+    /// The axis pushed past [PAD_AXIS_THRESHOLD], towards its positive end if `true`
+    Axis(GamepadAxisType, bool),
+    /// Button without a known layout, by its raw code, like the panels of most dance mats
+    Code(u32),
+}
+
+const PAD_BUTTONS: [GamepadButtonType; 19] = [
+    GamepadButtonType::South,
+    GamepadButtonType::East,
+    GamepadButtonType::North,
+    GamepadButtonType::West,
+    GamepadButtonType::C,
+    GamepadButtonType::Z,
+    GamepadButtonType::LeftTrigger,
+    GamepadButtonType::LeftTrigger2,
+    GamepadButtonType::RightTrigger,
+    GamepadButtonType::RightTrigger2,
+    GamepadButtonType::Select,
+    GamepadButtonType::Start,
+    GamepadButtonType::Mode,
+    GamepadButtonType::LeftThumb,
+    GamepadButtonType::RightThumb,
+    GamepadButtonType::DPadUp,
+    GamepadButtonType::DPadDown,
+    GamepadButtonType::DPadLeft,
+    GamepadButtonType::DPadRight,
+];
+
+const PAD_AXES: [GamepadAxisType; 8] = [
+    GamepadAxisType::LeftStickX,
+    GamepadAxisType::LeftStickY,
+    GamepadAxisType::LeftZ,
+    GamepadAxisType::RightStickX,
+    GamepadAxisType::RightStickY,
+    GamepadAxisType::RightZ,
+    GamepadAxisType::DPadX,
+    GamepadAxisType::DPadY,
+];
+
+impl PadInput {
+    fn all() -> impl Iterator<Item = PadInput> {
+        let buttons = PAD_BUTTONS.iter().map(|button| PadInput::Button(*button));
+        let axes = PAD_AXES.iter().flat_map(|axis| {
+            [true, false]
+                .into_iter()
+                .map(move |positive| PadInput::Axis(*axis, positive))
+        });
+        buttons.chain(axes)
+    }
+}
+
+/// Written as the name of the button, the name of the axis followed by `+` or `-`,
+/// or `Code` followed by the raw code
+impl fmt::Display for PadInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PadInput::Button(button) => write!(f, "{:?}", button),
+            PadInput::Axis(axis, true) => write!(f, "{:?}+", axis),
+            PadInput::Axis(axis, false) => write!(f, "{:?}-", axis),
+            PadInput::Code(code) => write!(f, "Code{}", code),
+        }
+    }
+}
+
+impl TryFrom<String> for PadInput {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        if let Some(code) = name.strip_prefix("Code").and_then(|code| code.parse().ok()) {
+            return Ok(PadInput::Code(code));
+        }
+        PadInput::all()
+            .find(|input| input.to_string() == name)
+            .ok_or_else(|| format!("unknown gamepad input {:?}", name))
+    }
+}
+
+impl From<PadInput> for String {
+    fn from(input: PadInput) -> Self {
+        input.to_string()
+    }
+}
+
+/// Names of the connected gamepads, which pick their binding profile
+#[derive(Default)]
+pub struct ConnectedPads(pub HashMap<Gamepad, String>);
+
+/// Gamepad inputs pressed this frame
+#[derive(Default)]
+pub struct PadPresses {
+    pub just_pressed: Vec<(Gamepad, PadInput)>,
+}
+
+/// Key or gamepad input, which can be bound to lanes
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Source {
+    Key(KeyCode),
+    Pad(Gamepad, PadInput),
+}
+
+/// A [Source] going down or up
+struct RawInput {
+    source: Source,
+    down: bool,
+    time: Instant,
+}
+
+/// Gamepad inputs read since the last frame, and the gamepads that went away
+#[derive(Default)]
+struct PadEvents {
+    inputs: Vec<RawInput>,
+    disconnected: Vec<(Gamepad, Instant)>,
+}
+
+/// Inputs held down
+#[derive(Default)]
+struct HeldInputs(HashSet<Source>);
+
+fn convert_button(button: gilrs::Button) -> Option<GamepadButtonType> {
+    use gilrs::Button::*;
+    Some(match button {
+        South => GamepadButtonType::South,
+        East => GamepadButtonType::East,
+        North => GamepadButtonType::North,
+        West => GamepadButtonType::West,
+        C => GamepadButtonType::C,
+        Z => GamepadButtonType::Z,
+        LeftTrigger => GamepadButtonType::LeftTrigger,
+        LeftTrigger2 => GamepadButtonType::LeftTrigger2,
+        RightTrigger => GamepadButtonType::RightTrigger,
+        RightTrigger2 => GamepadButtonType::RightTrigger2,
+        Select => GamepadButtonType::Select,
+        Start => GamepadButtonType::Start,
+        Mode => GamepadButtonType::Mode,
+        LeftThumb => GamepadButtonType::LeftThumb,
+        RightThumb => GamepadButtonType::RightThumb,
+        DPadUp => GamepadButtonType::DPadUp,
+        DPadDown => GamepadButtonType::DPadDown,
+        DPadLeft => GamepadButtonType::DPadLeft,
+        DPadRight => GamepadButtonType::DPadRight,
+        Unknown => return None,
+    })
+}
+
+fn convert_axis(axis: gilrs::Axis) -> Option<GamepadAxisType> {
+    use gilrs::Axis::*;
+    Some(match axis {
+        LeftStickX => GamepadAxisType::LeftStickX,
+        LeftStickY => GamepadAxisType::LeftStickY,
+        LeftZ => GamepadAxisType::LeftZ,
+        RightStickX => GamepadAxisType::RightStickX,
+        RightStickY => GamepadAxisType::RightStickY,
+        RightZ => GamepadAxisType::RightZ,
+        DPadX => GamepadAxisType::DPadX,
+        DPadY => GamepadAxisType::DPadY,
+        Unknown => return None,
+    })
+}
+
+/// Reads the gamepad events into [PadEvents] with the time the system gave them,
+/// and keeps [ConnectedPads] up to date
+fn read_gamepads(world: &mut World) {
+    let world = world.cell();
+    let mut gilrs = world
+        .get_non_send_mut::<Gilrs>()
+        .expect("Gilrs should be inserted");
+    let mut pad_events = world
+        .get_resource_mut::<PadEvents>()
+        .expect("PadEvents should be inserted");
+
+    let now = Instant::now();
+    let system_now = SystemTime::now();
+    while let Some(event) = gilrs.next_event() {
+        let age = system_now.duration_since(event.time).unwrap_or_default();
+        let time = now.checked_sub(age).unwrap_or(now);
+        let gamepad = Gamepad(event.id.into());
+        let mut push = |input, down| {
+            pad_events.inputs.push(RawInput {
+                source: Source::Pad(gamepad, input),
+                down,
+                time,
+            })
+        };
+
+        match event.event {
+            EventType::ButtonPressed(button, code) | EventType::ButtonReleased(button, code) => {
+                let input = convert_button(button)
+                    .map_or(PadInput::Code(code.into_u32()), PadInput::Button);
+                push(input, matches!(event.event, EventType::ButtonPressed(..)));
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                if let Some(axis) = convert_axis(axis) {
+                    push(PadInput::Axis(axis, true), value >= PAD_AXIS_THRESHOLD);
+                    push(PadInput::Axis(axis, false), value <= -PAD_AXIS_THRESHOLD);
+                }
+            }
+            EventType::Disconnected => pad_events.disconnected.push((gamepad, time)),
+            _ => {}
+        }
+    }
+
+    let names: HashMap<_, _> = gilrs
+        .gamepads()
+        .map(|(id, gamepad)| (Gamepad(id.into()), gamepad.name().to_string()))
+        .collect();
+    let mut pads = world
+        .get_resource_mut::<ConnectedPads>()
+        .expect("ConnectedPads should be inserted");
+    if pads.0 != names {
+        pads.0 = names;
+    }
+}
+
+/// Turns the inputs of the frame, in the order they happened, into presses and releases of the lanes
+#[allow(clippy::too_many_arguments)]
+fn update_lane_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut pad_events: ResMut<PadEvents>,
+    pads: Res<ConnectedPads>,
+    key_bindings: Res<KeyBindings>,
+    gamepad_bindings: Res<GamepadBindings>,
+    mut held: ResMut<HeldInputs>,
+    mut presses: ResMut<PadPresses>,
+    mut lanes: ResMut<Input<Directions>>,
+    mut lane_events: EventWriter<LaneEvent>,
+) {
+    // The window doesn't say when keys were pressed, so they count as pressed when the frame
+    // reads them, which can be a frame late
+    let now = Instant::now();
+    let mut inputs: Vec<_> = keyboard_events
+        .iter()
+        .filter_map(|event| {
+            Some(RawInput {
+                source: Source::Key(event.key_code?),
+                down: event.state == ElementState::Pressed,
+                time: now,
+            })
+        })
+        .collect();
+    inputs.append(&mut pad_events.inputs);
+    inputs.sort_by_key(|input| input.time);
+
+    let is_bound = |source: &Source, direction| match source {
+        Source::Key(key) => key_bindings.keys(direction).contains(key),
+        Source::Pad(gamepad, input) => pads.0.get(gamepad).is_some_and(|name| {
+            gamepad_bindings
+                .profile(name)
+                .keys(direction)
+                .contains(input)
+        }),
+    };
+
+    presses.just_pressed.clear();
+    lanes.update();
+    for input in inputs {
+        let changed = if input.down {
+            held.0.insert(input.source)
+        } else {
+            held.0.remove(&input.source)
+        };
+        if !changed {
+            continue;
+        }
+        if let (Source::Pad(gamepad, pad_input), true) = (input.source, input.down) {
+            presses.just_pressed.push((gamepad, pad_input));
+        }
+
+        for direction in LANES.iter().copied() {
+            if !is_bound(&input.source, direction) {
+                continue;
+            }
+
+            if input.down {
+                lanes.press(direction);
+            } else if lanes.pressed(direction)
+                && !held.0.iter().any(|source| is_bound(source, direction))
+            {
+                lanes.release(direction);
+            } else {
+                continue;
+            }
+            lane_events.send(LaneEvent {
+                direction,
+                pressed: input.down,
+                time: input.time,
+            });
+        }
+    }
+
+    // Nothing stays held on a gamepad that's gone. It's no longer connected, so its lanes are
+    // found by what's still held rather than by its bindings
+    for (gamepad, time) in pad_events.disconnected.drain(..) {
+        held.0
+            .retain(|source| !matches!(source, Source::Pad(pad, _) if *pad == gamepad));
+        for direction in LANES.iter().copied() {
+            if lanes.pressed(direction) && !held.0.iter().any(|source| is_bound(source, direction))
+            {
+                lanes.release(direction);
+                lane_events.send(LaneEvent {
+                    direction,
+                    pressed: false,
+                    time,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_bindings::PadProfile;
+    use bevy::app::Events;
+
+    fn pad_input(input: PadInput, down: bool) -> RawInput {
+        RawInput {
+            source: Source::Pad(Gamepad(0), input),
+            down,
+            time: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn disconnecting_releases_the_pad_lanes() {
+        let mut gamepad_bindings = GamepadBindings::default();
+        gamepad_bindings.devices.insert(
+            "Mat".to_string(),
+            PadProfile {
+                up: vec![PadInput::Code(3)],
+                down: Vec::new(),
+                left: vec![PadInput::Button(GamepadButtonType::DPadLeft)],
+                right: Vec::new(),
+            },
+        );
+        let mut pads = ConnectedPads::default();
+        pads.0.insert(Gamepad(0), "Mat".to_string());
+
+        let mut world = World::default();
+        world.insert_resource(Events::<KeyboardInput>::default());
+        world.insert_resource(Events::<LaneEvent>::default());
+        world.insert_resource(pads);
+        world.insert_resource(KeyBindings::default());
+        world.insert_resource(gamepad_bindings);
+        world.insert_resource(PadEvents::default());
+        world.insert_resource(HeldInputs::default());
+        world.insert_resource(PadPresses::default());
+        world.insert_resource(Input::<Directions>::default());
+        let mut stage = SystemStage::single(update_lane_input.system());
+
+        // Left is held on both the keyboard and the mat
+        world
+            .get_resource_mut::<Events<KeyboardInput>>()
+            .unwrap()
+            .send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(KeyCode::Left),
+                state: ElementState::Pressed,
+            });
+        world.get_resource_mut::<PadEvents>().unwrap().inputs = vec![
+            pad_input(PadInput::Code(3), true),
+            pad_input(PadInput::Button(GamepadButtonType::DPadLeft), true),
+        ];
+        stage.run(&mut world);
+        let lanes = world.get_resource::<Input<Directions>>().unwrap();
+        assert!(lanes.pressed(Directions::Up) && lanes.pressed(Directions::Left));
+
+        let events = world.get_resource::<Events<LaneEvent>>().unwrap();
+        let mut lane_events = events.get_reader();
+        assert_eq!(lane_events.iter(events).count(), 3);
+
+        world.get_resource_mut::<ConnectedPads>().unwrap().0.clear();
+        world
+            .get_resource_mut::<PadEvents>()
+            .unwrap()
+            .disconnected
+            .push((Gamepad(0), Instant::now()));
+        stage.run(&mut world);
+
+        let lanes = world.get_resource::<Input<Directions>>().unwrap();
+        assert!(!lanes.pressed(Directions::Up));
+        assert!(lanes.pressed(Directions::Left));
+        let held = world.get_resource::<HeldInputs>().unwrap();
+        assert!(held
+            .0
+            .iter()
+            .all(|source| *source == Source::Key(KeyCode::Left)));
+
+        let events = world.get_resource::<Events<LaneEvent>>().unwrap();
+        let releases: Vec<_> = lane_events
+            .iter(events)
+            .map(|event| (event.direction, event.pressed))
+            .collect();
+        assert_eq!(releases, [(Directions::Up, false)]);
+    }
+}
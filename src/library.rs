@@ -0,0 +1,269 @@
+use crate::{
+    consts::*,
+    high_scores::chart_hash,
+    types::{load_config_toml, song_file_path, Difficulty, SongConfigToml, SongMetadata},
+};
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+pub struct LibraryPlugin;
+impl Plugin for LibraryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Library::load());
+    }
+}
+
+/// Songs in `assets/songs` and its folders. Each folder with a song file is a song,
+/// and the folder holding song folders is their pack
+#[derive(Default)]
+pub struct Library {
+    /// Sorted by pack, then by path
+    pub songs: Vec<LibrarySong>,
+    /// Why each song file that couldn't be loaded was broken
+    pub broken: Vec<String>,
+    /// Songs of the last scan by path, saved in [LIBRARY_CACHE_PATH] so unchanged files aren't read again
+    cache: BTreeMap<String, CachedSong>,
+}
+
+/// What the menu shows about a song, without its arrows
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LibrarySong {
+    /// Song file, relative to `assets/songs`
+    pub path: String,
+    /// Folder of the pack, relative to `assets/songs`. Empty for songs outside packs
+    pub pack: String,
+    pub name: String,
+    /// Audio file, relative to the folder of the song file
+    pub filename: String,
+    pub metadata: SongMetadata,
+    /// Sorted by difficulty
+    pub charts: Vec<LibraryChart>,
+    /// [chart_hash] of the song file, which its high scores are kept under
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibraryChart {
+    pub difficulty: Difficulty,
+    pub rating: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct CachedSong {
+    /// Nanoseconds since the epoch at which the file was last modified
+    modified: u64,
+    size: u64,
+    /// Same for the audio file, which the song needs to be playable
+    #[serde(default)]
+    audio: Option<(u64, u64)>,
+    song: LibrarySong,
+}
+
+impl LibrarySong {
+    fn new(path: String, config: SongConfigToml) -> Self {
+        // The pack holds the folder of the song file
+        let pack = Path::new(&path)
+            .parent()
+            .and_then(|folder| folder.parent())
+            .map_or(String::new(), |pack| pack.display().to_string());
+
+        let mut charts: Vec<_> = config
+            .charts
+            .iter()
+            .map(|chart| LibraryChart {
+                difficulty: chart.difficulty,
+                rating: chart.rating,
+            })
+            .collect();
+        charts.sort_by_key(|chart| chart.difficulty);
+
+        let hash = chart_hash(&path);
+        Self {
+            path,
+            pack,
+            name: config.name,
+            filename: config.filename,
+            metadata: config.metadata,
+            charts,
+            hash,
+        }
+    }
+}
+
+impl Library {
+    fn load() -> Self {
+        let cache = match fs::read_to_string(LIBRARY_CACHE_PATH) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|err| {
+                warn!(
+                    "Couldn't parse {}, ignoring it: {}",
+                    LIBRARY_CACHE_PATH, err
+                );
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+
+        Self {
+            cache,
+            ..Default::default()
+        }
+    }
+
+    fn save(&self) {
+        // Going through a value writes the songs in an order TOML accepts
+        let result = toml::Value::try_from(&self.cache)
+            .map_err(|err| err.to_string())
+            .and_then(|cache| {
+                if let Some(dir) = Path::new(LIBRARY_CACHE_PATH).parent() {
+                    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+                }
+                fs::write(LIBRARY_CACHE_PATH, cache.to_string()).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            error!("Couldn't save the song library: {}", err);
+        }
+    }
+
+    /// Finds the songs in `assets/songs`, only reading the song files that changed since the last scan
+    pub fn scan(&mut self) {
+        let mut cache = BTreeMap::new();
+        let mut songs = Vec::new();
+        let mut broken = Vec::new();
+        let mut changed = false;
+
+        for file in walk_songs_dir() {
+            if file.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let path = match file.strip_prefix("assets/songs") {
+                Ok(path) => path.display().to_string(),
+                Err(_) => continue,
+            };
+
+            let stamp = file_stamp(&file);
+            let song = match self.cache.remove(&path) {
+                Some(cached)
+                    if Some((cached.modified, cached.size)) == stamp
+                        && cached.audio.is_some()
+                        && cached.audio == audio_stamp(&cached.song)
+                        && cached.song.hash.is_some() =>
+                {
+                    cached.song
+                }
+                _ => {
+                    changed = true;
+                    match load_config_toml(&path) {
+                        Ok(config) => LibrarySong::new(path.clone(), config),
+                        Err(err) => {
+                            warn!("{}", err);
+                            broken.push(err.to_string());
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if let Some((modified, size)) = stamp {
+                cache.insert(
+                    path,
+                    CachedSong {
+                        modified,
+                        size,
+                        audio: audio_stamp(&song),
+                        song: song.clone(),
+                    },
+                );
+            }
+            songs.push(song);
+        }
+        songs.sort_by(|a, b| a.pack.cmp(&b.pack).then_with(|| a.path.cmp(&b.path)));
+
+        // Songs left in the old cache were removed
+        changed |= !self.cache.is_empty();
+        self.songs = songs;
+        self.broken = broken;
+        self.cache = cache;
+        if changed {
+            self.save();
+        }
+    }
+
+    /// Names of the packs, sorted
+    pub fn packs(&self) -> Vec<&str> {
+        let mut packs: Vec<_> = self
+            .songs
+            .iter()
+            .map(|song| song.pack.as_str())
+            .filter(|pack| !pack.is_empty())
+            .collect();
+        packs.dedup();
+        packs
+    }
+
+    pub fn pack_songs<'a>(
+        &'a self,
+        pack: &'a str,
+    ) -> impl Iterator<Item = &'a LibrarySong> + Clone {
+        self.songs.iter().filter(move |song| song.pack == pack)
+    }
+}
+
+/// When the file was last modified and its size, if they can be read
+fn file_stamp(file: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos() as u64;
+    Some((modified, metadata.len()))
+}
+
+/// [file_stamp] of the audio file of `song`
+fn audio_stamp(song: &LibrarySong) -> Option<(u64, u64)> {
+    file_stamp(&Path::new("assets/songs").join(song_file_path(&song.path, &song.filename)))
+}
+
+/// Files in `assets/songs` and its folders, skipping hidden ones. Logs folders that can't be read
+pub fn walk_songs_dir() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut folders = vec![PathBuf::from("assets/songs")];
+
+    while let Some(folder) = folders.pop() {
+        let entries = match fs::read_dir(&folder) {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("Couldn't read {}: {}", folder.display(), err);
+                continue;
+            }
+        };
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let hidden = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with('.'));
+            if hidden {
+                continue;
+            }
+
+            if path.is_dir() {
+                folders.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    files
+}
@@ -1,32 +1,69 @@
 use arrow::ArrowsPlugin;
 use audio::AudioPlugin;
-use bevy::{input::system::exit_on_esc_system, prelude::*};
+use bevy::{gilrs::GilrsPlugin, input::system::exit_on_esc_system, prelude::*};
+use calibration::CalibrationPlugin;
+use chart_loader::ChartLoaderPlugin;
+use clock::ClockPlugin;
 use consts::AppState;
+use editor::EditorPlugin;
+use high_scores::HighScoresPlugin;
+use key_bindings::KeyBindingsPlugin;
+use lane_input::LaneInputPlugin;
+use library::LibraryPlugin;
+use map_maker::MapMakerPlugin;
 use menu::MenuPlugin;
+use pause::PausePlugin;
+use results::ResultsPlugin;
 use score::ScoreResource;
+use settings::SettingsPlugin;
 use shaders::ShadersPlugin;
+use song_offsets::SongOffsetsPlugin;
 use ui::UIPlugin;
 
 pub mod arrow;
 pub mod audio;
+pub mod calibration;
+pub mod chart_loader;
+pub mod clock;
 pub mod consts;
+pub mod editor;
+pub mod high_scores;
+pub mod import;
+pub mod key_bindings;
+pub mod lane_input;
+pub mod library;
+pub mod map_maker;
 pub mod menu;
+pub mod pause;
+pub mod results;
 pub mod score;
+pub mod settings;
 pub mod shaders;
+pub mod song_offsets;
+pub mod tempo;
 pub mod types;
 pub mod ui;
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let config = types::load_config("test.toml", &asset_server);
+fn setup(mut commands: Commands) {
     commands
         .spawn_bundle(OrthographicCameraBundle::new_2d())
         .commands()
-        .spawn_bundle(UiCameraBundle::default())
-        .commands()
-        .insert_resource(config);
+        .spawn_bundle(UiCameraBundle::default());
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("convert") {
+        match import::convert_command(&args[1..]) {
+            Ok(path) => println!("Wrote {}", path),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     App::build()
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(WindowDescriptor {
@@ -38,12 +75,28 @@ fn main() {
         .add_state(AppState::Menu)
         .init_resource::<ScoreResource>()
         .add_startup_system(setup.system())
-        .add_system(exit_on_esc_system.system())
-        .add_plugins(DefaultPlugins)
+        .add_system_set(
+            SystemSet::on_update(AppState::Menu).with_system(exit_on_esc_system.system()),
+        )
+        // Gamepads are read by LaneInputPlugin, which keeps the time of their events
+        .add_plugins_with(DefaultPlugins, |group| group.disable::<GilrsPlugin>())
+        .add_plugin(ChartLoaderPlugin)
+        .add_plugin(ClockPlugin)
         .add_plugin(ArrowsPlugin)
         .add_plugin(UIPlugin)
         .add_plugin(AudioPlugin)
         .add_plugin(ShadersPlugin)
         .add_plugin(MenuPlugin)
+        .add_plugin(MapMakerPlugin)
+        .add_plugin(EditorPlugin)
+        .add_plugin(ResultsPlugin)
+        .add_plugin(PausePlugin)
+        .add_plugin(HighScoresPlugin)
+        .add_plugin(LibraryPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(KeyBindingsPlugin)
+        .add_plugin(LaneInputPlugin)
+        .add_plugin(CalibrationPlugin)
+        .add_plugin(SongOffsetsPlugin)
         .run();
 }
@@ -0,0 +1,269 @@
+use crate::{
+    audio::SongPlayer,
+    calibration::Calibration,
+    clock::{SongClock, SongClockLabel},
+    consts::*,
+    lane_input::LaneEvent,
+    types::{ArrowTimeToml, ChartToml, Difficulty, SongConfigToml, SongMetadata, Speed},
+};
+use bevy::prelude::*;
+use std::{fs, path::Path};
+
+pub struct MapMakerPlugin;
+impl Plugin for MapMakerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::MakeMap).with_system(setup_map_maker.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::MakeMap)
+                .with_system(start_recording_song.system().after(SongClockLabel))
+                .with_system(record_arrows.system().after(SongClockLabel))
+                .with_system(update_recorder_text.system())
+                .with_system(finish_recording.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::MakeMap).with_system(despawn_map_maker.system()),
+        );
+    }
+}
+
+/// Song to record a map for, inserted by the menu before entering [AppState::MakeMap].
+/// When it's missing, the chart editor runs instead of the recorder
+pub struct MapMakerSong {
+    /// Audio file name, relative to `assets/songs`
+    pub filename: String,
+}
+
+/// Arrows recorded so far
+struct Recording {
+    speed: Speed,
+    arrows: Vec<ArrowTimeToml>,
+    /// Arrows whose key is still held down, they become hold arrows if held long enough
+    held: Vec<usize>,
+}
+
+struct MapMakerUI;
+struct RecorderText;
+
+impl RecorderText {
+    fn get_text(filename: &str, speed: Speed, arrows: usize) -> String {
+        format!(
+            "Recording: {}\nSpeed: {:?}, Arrows: {}\n1/2/3: change speed, hold a key: hold arrow, Enter: save and exit",
+            filename, speed, arrows
+        )
+    }
+}
+
+fn setup_map_maker(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    song: Option<Res<MapMakerSong>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let song = match song {
+        Some(song) => song,
+        None => return,
+    };
+
+    commands.insert_resource(Recording {
+        speed: Speed::Slow,
+        arrows: Vec::new(),
+        held: Vec::new(),
+    });
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: color_materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(MapMakerUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        RecorderText::get_text(&song.filename, Speed::Slow, 0),
+                        TextStyle {
+                            font,
+                            font_size: 30.,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(RecorderText);
+        });
+}
+
+fn start_recording_song(
+    mut player: ResMut<SongPlayer>,
+    clock: Res<SongClock>,
+    song: Option<Res<MapMakerSong>>,
+    asset_server: Res<AssetServer>,
+    calibration: Res<Calibration>,
+) {
+    let song = match song {
+        Some(song) => song,
+        None => return,
+    };
+
+    if clock.just_passed(calibration.audio_start(&clock)) {
+        player.play(asset_server.load(&*format!("songs/{}", song.filename)));
+    }
+}
+
+fn record_arrows(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut lane_events: EventReader<LaneEvent>,
+    clock: Res<SongClock>,
+    recording: Option<ResMut<Recording>>,
+) {
+    let mut recording = match recording {
+        Some(recording) => recording,
+        None => return,
+    };
+
+    if keyboard_input.just_pressed(KeyCode::Key1) {
+        recording.speed = Speed::Slow;
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        recording.speed = Speed::Medium;
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        recording.speed = Speed::Fast;
+    }
+
+    let recording = &mut *recording;
+    for event in lane_events.iter() {
+        // Nothing can be clicked before the song starts
+        let click_time = clock.seconds_at(event.time);
+        if click_time < 0. {
+            continue;
+        }
+
+        if event.pressed {
+            let speed = recording.speed;
+            recording.arrows.push(ArrowTimeToml {
+                click_time,
+                beat: None,
+                speed,
+                direction: event.direction,
+                duration: None,
+            });
+            recording.held.push(recording.arrows.len() - 1);
+            continue;
+        }
+
+        recording.held.retain(|index| {
+            let arrow = &mut recording.arrows[*index];
+            if arrow.direction != event.direction {
+                return true;
+            }
+
+            let duration = click_time - arrow.click_time;
+            if duration >= MIN_HOLD_DURATION {
+                arrow.duration = Some(duration);
+            }
+            false
+        });
+    }
+}
+
+fn update_recorder_text(
+    recording: Option<Res<Recording>>,
+    song: Option<Res<MapMakerSong>>,
+    mut query: Query<&mut Text, With<RecorderText>>,
+) {
+    let (recording, song) = match (recording, song) {
+        (Some(recording), Some(song)) => (recording, song),
+        _ => return,
+    };
+
+    if recording.is_changed() {
+        for mut text in query.iter_mut() {
+            text.sections[0].value =
+                RecorderText::get_text(&song.filename, recording.speed, recording.arrows.len());
+        }
+    }
+}
+
+fn finish_recording(
+    keyboard_input: Res<Input<KeyCode>>,
+    song: Option<Res<MapMakerSong>>,
+    recording: Option<ResMut<Recording>>,
+    mut player: ResMut<SongPlayer>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let (song, mut recording) = match (song, recording) {
+        (Some(song), Some(recording)) => (song, recording),
+        _ => return,
+    };
+
+    if !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    player.stop();
+
+    let name = Path::new(&song.filename)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("map")
+        .to_string();
+    let config = SongConfigToml {
+        name: name.clone(),
+        metadata: SongMetadata::default(),
+        filename: song.filename.clone(),
+        offset: 0.,
+        bpm: None,
+        bpm_changes: Vec::new(),
+        time_signatures: Vec::new(),
+        arrows: Vec::new(),
+        charts: vec![ChartToml {
+            difficulty: Difficulty::Normal,
+            rating: 0,
+            arrows: std::mem::take(&mut recording.arrows),
+        }],
+    };
+
+    match save_map(&name, &config) {
+        Ok(path) => info!("Saved map to {}", path),
+        Err(err) => error!("Couldn't save map: {}", err),
+    }
+
+    state
+        .set(AppState::Menu)
+        .expect("Couldn't switch state to Menu");
+}
+
+/// Writes the map into `assets/songs`, without overwriting existing maps
+pub fn save_map(name: &str, config: &SongConfigToml) -> Result<String, Box<dyn std::error::Error>> {
+    let contents = config.to_toml();
+
+    let mut path = format!("assets/songs/{}.toml", name);
+    let mut counter = 1;
+    while Path::new(&path).exists() {
+        counter += 1;
+        path = format!("assets/songs/{}_{}.toml", name, counter);
+    }
+
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+fn despawn_map_maker(mut commands: Commands, query: Query<Entity, With<MapMakerUI>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Recording>();
+    commands.remove_resource::<MapMakerSong>();
+}
@@ -1,27 +1,48 @@
-use crate::{consts::*, types::load_config};
-use bevy::prelude::*;
-use std::fs::read_dir;
+use crate::{
+    audio::PreviewPlayer,
+    consts::*,
+    editor::EditMap,
+    high_scores::HighScores,
+    import::{import_file, is_importable},
+    key_bindings::KeyBindings,
+    library::{walk_songs_dir, Library, LibraryChart, LibrarySong},
+    map_maker::{save_map, MapMakerSong},
+    song_offsets::{offset_nudge, SongOffsets},
+    types::{song_file_path, Difficulty, SongConfig, SongConfigToml, SongMetadata},
+};
+use bevy::{asset::LoadState, prelude::*};
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
 
 pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ButtonMaterials>()
+            .init_resource::<MenuSelection>()
             .add_system_set(SystemSet::on_enter(AppState::Menu).with_system(setup_menu.system()))
             .add_system_set(
                 SystemSet::on_update(AppState::Menu)
                     .with_system(button_color_system.system())
-                    .with_system(button_press_system.system()),
+                    .with_system(button_press_system.system())
+                    .with_system(preview_hovered_song.system())
+                    .with_system(nudge_hovered_offset.system())
+                    .with_system(turn_page.system())
+                    .with_system(play_loaded_song.system())
+                    .with_system(rebuild_menu.system()),
             )
             .add_system_set(SystemSet::on_exit(AppState::Menu).with_system(despawn_menu.system()));
     }
 }
 
-struct ButtonMaterials {
-    none: Handle<ColorMaterial>,
-    normal: Handle<ColorMaterial>,
-    hovered: Handle<ColorMaterial>,
-    pressed: Handle<ColorMaterial>,
-    font: Handle<Font>,
+pub struct ButtonMaterials {
+    pub none: Handle<ColorMaterial>,
+    pub normal: Handle<ColorMaterial>,
+    pub hovered: Handle<ColorMaterial>,
+    pub pressed: Handle<ColorMaterial>,
+    pub disabled: Handle<ColorMaterial>,
+    pub font: Handle<Font>,
 }
 
 impl FromWorld for ButtonMaterials {
@@ -36,6 +57,7 @@ impl FromWorld for ButtonMaterials {
             normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
             hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
             pressed: materials.add(Color::rgb(0.35, 0.75, 0.35).into()),
+            disabled: materials.add(Color::rgb(0.3, 0.1, 0.1).into()),
             font,
         }
     }
@@ -44,25 +66,240 @@ impl FromWorld for ButtonMaterials {
 struct MenuUI;
 
 enum MenuButton {
-    MakeMap,
-    PlaySong(String),
+    MakeMap(String),
+    EditMap(Box<LibrarySong>),
+    PlaySong(Box<LibrarySong>),
+    /// Lists the songs of a pack
+    Pack {
+        pack: String,
+        songs: usize,
+    },
+    /// Chart of the song picked with [MenuButton::PlaySong] or [MenuButton::EditMap]
+    PickDifficulty {
+        path: String,
+        /// [LibrarySong::hash] of the song, for its high scores
+        hash: Option<String>,
+        chart: LibraryChart,
+        edit: bool,
+    },
+    /// Goes back to the list the current one was opened from
+    Back,
+    /// Chart from another game in `assets/songs`, converted to our format when clicked
+    Import(String),
+    /// Song file that couldn't be loaded, with the reason. Can't be clicked
+    Broken(String),
+    /// Opens the screen to change the keys of each lane
+    KeyBindings,
+    /// Opens the screen measuring the audio and visual offsets
+    Calibration,
+    /// Shows another page of a list too long for the screen
+    Page {
+        page: usize,
+        pages: usize,
+        next: bool,
+    },
 }
 
 impl MenuButton {
-    fn name(&self) -> String {
+    fn name(&self, high_scores: &HighScores, song_offsets: &SongOffsets) -> String {
         match self {
-            MenuButton::MakeMap => "Make map".to_string(),
-            MenuButton::PlaySong(song) => format!("Play song: {}", song),
+            MenuButton::MakeMap(filename) => format!("Make map: {}", filename),
+            MenuButton::EditMap(song) => format!("Edit map: {}", song.path),
+            MenuButton::PlaySong(song) => {
+                let mut text = format!("Play song: {}", song.name);
+                if let Some(artist) = &song.metadata.artist {
+                    text += &format!(" - {}", artist);
+                }
+                let details = song_details(&song.metadata);
+                if !details.is_empty() {
+                    text += &format!("\n{}", details);
+                }
+                text += &offset_line(song_offsets, &song.path);
+
+                match &song.charts[..] {
+                    [chart] => {
+                        text + &best_line(high_scores, song.hash.as_deref(), chart.difficulty)
+                    }
+                    charts => format!(
+                        "{}\n{}",
+                        text,
+                        charts
+                            .iter()
+                            .map(|chart| format!("{:?} {}", chart.difficulty, chart.rating))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            }
+            MenuButton::Pack { pack, songs } => format!("Pack: {} ({} songs)", pack, songs),
+            MenuButton::PickDifficulty { hash, chart, .. } => format!(
+                "{:?} {}{}",
+                chart.difficulty,
+                chart.rating,
+                best_line(high_scores, hash.as_deref(), chart.difficulty)
+            ),
+            MenuButton::Back => "Back".to_string(),
+            MenuButton::Import(filename) => format!("Import chart: {}", filename),
+            MenuButton::Broken(reason) => format!("Broken chart: {}", reason),
+            MenuButton::KeyBindings => "Key bindings".to_string(),
+            MenuButton::Calibration => "Calibrate offsets".to_string(),
+            MenuButton::Page { page, pages, next } => format!(
+                "{} ({}/{})",
+                if *next {
+                    "Next page (PageDown)"
+                } else {
+                    "Previous page (PageUp)"
+                },
+                page + 1,
+                pages
+            ),
         }
     }
 }
 
-fn setup_menu(mut commands: Commands, button_materials: Res<ButtonMaterials>) {
-    let mut buttons: Vec<_> = get_songs_iter()
-        .map(|name| MenuButton::PlaySong(name))
-        .collect();
+/// Genre, length and charter of a song, those it has
+fn song_details(metadata: &SongMetadata) -> String {
+    let mut details = Vec::new();
+    if let Some(genre) = &metadata.genre {
+        details.push(genre.clone());
+    }
+    if let Some(length) = metadata.length {
+        let secs = length.round() as u64;
+        details.push(format!("{}:{:02}", secs / 60, secs % 60));
+    }
+    if let Some(charter) = &metadata.charter {
+        details.push(format!("charted by {}", charter));
+    }
+    details.join(" | ")
+}
+
+/// Offset of a song on its own line, or nothing if it has none
+fn offset_line(song_offsets: &SongOffsets, path: &str) -> String {
+    let offset = song_offsets.get(path);
+    if offset == 0. {
+        String::new()
+    } else {
+        format!("\nOffset: {:+.0} ms", offset)
+    }
+}
+
+/// High score of a chart on its own line, or nothing if it was never played
+fn best_line(high_scores: &HighScores, hash: Option<&str>, difficulty: Difficulty) -> String {
+    match hash.and_then(|hash| high_scores.get(hash, difficulty)) {
+        Some(best) => format!(
+            "\nBest: {} ({:.2}% {}), combo {}, {}",
+            best.score,
+            best.accuracy * 100.,
+            best.grade,
+            best.max_combo,
+            best.date
+        ),
+        None => String::new(),
+    }
+}
 
-    buttons.push(MenuButton::MakeMap);
+#[allow(clippy::too_many_arguments)]
+fn setup_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    button_materials: Res<ButtonMaterials>,
+    high_scores: Res<HighScores>,
+    song_offsets: Res<SongOffsets>,
+    mut library: ResMut<Library>,
+    selection: Res<MenuSelection>,
+) {
+    library.scan();
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        &mut materials,
+        &button_materials,
+        &high_scores,
+        &song_offsets,
+        &library,
+        &selection,
+    );
+}
+
+/// Asks for the menu to be rebuilt, after the songs or the selection changed
+struct RebuildMenu;
+
+/// Which list the menu shows, the songs outside packs and the packs when nothing is selected
+#[derive(Default)]
+struct MenuSelection {
+    /// Pack whose songs are listed
+    pack: Option<String>,
+    /// Song whose difficulties are listed
+    song: Option<SelectedSong>,
+    /// Page of the list of packs or songs, the difficulties fit on one
+    page: usize,
+}
+
+struct SelectedSong {
+    song: LibrarySong,
+    edit: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rebuild_menu(
+    mut commands: Commands,
+    rebuild: Option<Res<RebuildMenu>>,
+    query: Query<Entity, With<MenuUI>>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    button_materials: Res<ButtonMaterials>,
+    high_scores: Res<HighScores>,
+    song_offsets: Res<SongOffsets>,
+    library: Res<Library>,
+    selection: Res<MenuSelection>,
+) {
+    if rebuild.is_none() {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        &mut materials,
+        &button_materials,
+        &high_scores,
+        &song_offsets,
+        &library,
+        &selection,
+    );
+    commands.remove_resource::<RebuildMenu>();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    materials: &mut Assets<ColorMaterial>,
+    button_materials: &ButtonMaterials,
+    high_scores: &HighScores,
+    song_offsets: &SongOffsets,
+    library: &Library,
+    selection: &MenuSelection,
+) {
+    let buttons = match selection {
+        MenuSelection {
+            song: Some(selected),
+            ..
+        } => difficulty_buttons(selected),
+        MenuSelection {
+            pack: Some(pack), ..
+        } => pack_buttons(library, pack),
+        _ => song_buttons(library),
+    };
+    let buttons = if selection.song.is_some() {
+        buttons
+    } else {
+        page_buttons(buttons, selection.page)
+    };
 
     commands
         .spawn_bundle(NodeBundle {
@@ -80,25 +317,69 @@ fn setup_menu(mut commands: Commands, button_materials: Res<ButtonMaterials>) {
         .insert(MenuUI)
         .with_children(|parent| {
             for button in buttons {
-                parent
-                    .spawn_bundle(ButtonBundle {
-                        style: Style {
-                            size: Size::new(Val::Px(350.0), Val::Px(65.0)),
-                            margin: Rect::all(Val::Auto),
-                            justify_content: JustifyContent::Center,
-                            align_items: AlignItems::Center,
-                            ..Default::default()
-                        },
+                // Buttons grow to fit the details of songs
+                let style = Style {
+                    size: Size::new(Val::Px(450.0), Val::Auto),
+                    min_size: Size::new(Val::Auto, Val::Px(65.0)),
+                    padding: Rect::all(Val::Px(5.0)),
+                    margin: Rect::all(Val::Auto),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                };
+
+                // Broken charts are listed, but aren't buttons
+                let (mut entity, font_size) = if let MenuButton::Broken(_) = button {
+                    let entity = parent.spawn_bundle(NodeBundle {
+                        style,
+                        material: button_materials.disabled.clone(),
+                        ..Default::default()
+                    });
+                    (entity, 12.0)
+                } else {
+                    let entity = parent.spawn_bundle(ButtonBundle {
+                        style,
                         material: button_materials.normal.clone(),
                         ..Default::default()
-                    })
+                    });
+                    (entity, 20.0)
+                };
+
+                let banner = match &button {
+                    MenuButton::PlaySong(song) => song
+                        .metadata
+                        .banner
+                        .as_ref()
+                        .map(|banner| song_file_path(&song.path, banner)),
+                    _ => None,
+                };
+                let banner = banner.map(|banner| {
+                    let texture = asset_server.load(&*format!("songs/{}", banner));
+                    materials.add(texture.into())
+                });
+
+                entity
                     .with_children(|parent| {
+                        if let Some(banner) = banner {
+                            parent.spawn_bundle(ImageBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(128.0), Val::Px(40.0)),
+                                    margin: Rect {
+                                        right: Val::Px(10.0),
+                                        ..Default::default()
+                                    },
+                                    ..Default::default()
+                                },
+                                material: banner,
+                                ..Default::default()
+                            });
+                        }
                         parent.spawn_bundle(TextBundle {
                             text: Text::with_section(
-                                button.name(),
+                                button.name(high_scores, song_offsets),
                                 TextStyle {
                                     font: button_materials.font.clone(),
-                                    font_size: 20.0,
+                                    font_size,
                                     color: Color::rgb(0.9, 0.9, 0.9),
                                 },
                                 Default::default(),
@@ -111,13 +392,147 @@ fn setup_menu(mut commands: Commands, button_materials: Res<ButtonMaterials>) {
         });
 }
 
-fn despawn_menu(mut commands: Commands, query: Query<Entity, With<MenuUI>>) {
+/// Play and edit buttons of `songs`
+fn song_list<'a>(songs: impl Iterator<Item = &'a LibrarySong> + Clone) -> Vec<MenuButton> {
+    let mut buttons: Vec<_> = songs
+        .clone()
+        .map(|song| MenuButton::PlaySong(Box::new(song.clone())))
+        .collect();
+    buttons.extend(songs.map(|song| MenuButton::EditMap(Box::new(song.clone()))));
+    buttons
+}
+
+/// Packs and songs outside packs, then what can be made into songs
+fn song_buttons(library: &Library) -> Vec<MenuButton> {
+    let mut buttons: Vec<_> = library
+        .packs()
+        .into_iter()
+        .map(|pack| MenuButton::Pack {
+            pack: pack.to_string(),
+            songs: library.pack_songs(pack).count(),
+        })
+        .collect();
+
+    buttons.extend(song_list(library.pack_songs("")));
+    buttons.extend(get_audio_iter().map(MenuButton::MakeMap));
+    buttons.extend(get_importable_iter().map(MenuButton::Import));
+    buttons.extend(library.broken.iter().cloned().map(MenuButton::Broken));
+    buttons.push(MenuButton::KeyBindings);
+    buttons.push(MenuButton::Calibration);
+    buttons
+}
+
+/// Keeps the buttons on `page`, with buttons to the pages around it.
+/// Going back and the settings are on every page
+fn page_buttons(buttons: Vec<MenuButton>, page: usize) -> Vec<MenuButton> {
+    let (mut list, always): (Vec<_>, Vec<_>) = buttons.into_iter().partition(|button| {
+        !matches!(
+            button,
+            MenuButton::Back | MenuButton::KeyBindings | MenuButton::Calibration
+        )
+    });
+
+    let pages = list.len().max(1).div_ceil(MENU_PAGE_SIZE);
+    let page = page.min(pages - 1);
+    let start = page * MENU_PAGE_SIZE;
+    let end = (start + MENU_PAGE_SIZE).min(list.len());
+
+    let mut buttons = Vec::new();
+    if page > 0 {
+        buttons.push(MenuButton::Page {
+            page: page - 1,
+            pages,
+            next: false,
+        });
+    }
+    buttons.extend(list.drain(start..end));
+    if page + 1 < pages {
+        buttons.push(MenuButton::Page {
+            page: page + 1,
+            pages,
+            next: true,
+        });
+    }
+    buttons.extend(always);
+    buttons
+}
+
+fn pack_buttons(library: &Library, pack: &str) -> Vec<MenuButton> {
+    let mut buttons = song_list(library.pack_songs(pack));
+    buttons.push(MenuButton::Back);
+    buttons
+}
+
+fn difficulty_buttons(selected: &SelectedSong) -> Vec<MenuButton> {
+    let mut buttons: Vec<_> = selected
+        .song
+        .charts
+        .iter()
+        .map(|chart| MenuButton::PickDifficulty {
+            path: selected.song.path.clone(),
+            hash: selected.song.hash.clone(),
+            chart: *chart,
+            edit: selected.edit,
+        })
+        .collect();
+    buttons.push(MenuButton::Back);
+    buttons
+}
+
+/// Song picked in the menu, the game starts once its chart is loaded
+struct LoadingSong {
+    path: String,
+    file: Handle<SongConfigToml>,
+    difficulty: Difficulty,
+}
+
+fn play_loaded_song(
+    mut commands: Commands,
+    loading: Option<Res<LoadingSong>>,
+    asset_server: Res<AssetServer>,
+    charts: Res<Assets<SongConfigToml>>,
+    song_offsets: Res<SongOffsets>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let loading = match loading {
+        Some(loading) => loading,
+        None => return,
+    };
+
+    if let Some(chart) = charts.get(&loading.file) {
+        commands.insert_resource(SongConfig::new(
+            loading.path.clone(),
+            loading.file.clone(),
+            chart,
+            loading.difficulty,
+            song_offsets.secs(&loading.path),
+            &asset_server,
+        ));
+        commands.remove_resource::<LoadingSong>();
+        state
+            .set(AppState::Game)
+            .expect("Couldn't switch state to Game");
+    } else if asset_server.get_load_state(&loading.file) == LoadState::Failed {
+        // The asset server already logged why
+        error!("Couldn't load the song {}", loading.path);
+        commands.remove_resource::<LoadingSong>();
+    }
+}
+
+fn despawn_menu(
+    mut commands: Commands,
+    query: Query<Entity, With<MenuUI>>,
+    mut selection: ResMut<MenuSelection>,
+) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
+    // Coming back to the menu shows the same pack
+    selection.song = None;
 }
 
-fn button_color_system(
+#[allow(clippy::type_complexity)]
+pub fn button_color_system(
     button_materials: Res<ButtonMaterials>,
     mut query: Query<
         (&Interaction, &mut Handle<ColorMaterial>),
@@ -133,43 +548,253 @@ fn button_color_system(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn button_press_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     query: Query<(&Interaction, &MenuButton), (Changed<Interaction>, With<Button>)>,
     mut state: ResMut<State<AppState>>,
+    mut selection: ResMut<MenuSelection>,
+    mut library: ResMut<Library>,
 ) {
     for (interaction, button) in query.iter() {
         if *interaction == Interaction::Clicked {
             match button {
-                MenuButton::MakeMap => state
-                    .set(AppState::MakeMap)
-                    .expect("Couldn't switch state to MakeMap"),
-                MenuButton::PlaySong(song) => {
-                    let config = load_config(&*format!("{}.toml", song), &asset_server);
-                    commands.insert_resource(config);
+                MenuButton::MakeMap(filename) => {
+                    commands.insert_resource(MapMakerSong {
+                        filename: filename.clone(),
+                    });
                     state
-                        .set(AppState::Game)
-                        .expect("Couldn't switch state to Game")
+                        .set(AppState::MakeMap)
+                        .expect("Couldn't switch state to MakeMap")
+                }
+                MenuButton::PlaySong(song) | MenuButton::EditMap(song) => {
+                    let edit = matches!(button, MenuButton::EditMap(_));
+                    // The difficulty is only asked when there's a choice
+                    match &song.charts[..] {
+                        [chart] => pick_chart(
+                            &mut commands,
+                            &asset_server,
+                            &mut state,
+                            &song.path,
+                            chart.difficulty,
+                            edit,
+                        ),
+                        _ => {
+                            selection.song = Some(SelectedSong {
+                                song: (**song).clone(),
+                                edit,
+                            });
+                            commands.insert_resource(RebuildMenu);
+                        }
+                    }
+                }
+                MenuButton::Pack { pack, .. } => {
+                    selection.pack = Some(pack.clone());
+                    selection.page = 0;
+                    commands.insert_resource(RebuildMenu);
                 }
+                MenuButton::PickDifficulty {
+                    path, chart, edit, ..
+                } => pick_chart(
+                    &mut commands,
+                    &asset_server,
+                    &mut state,
+                    path,
+                    chart.difficulty,
+                    *edit,
+                ),
+                MenuButton::Back => {
+                    if selection.song.take().is_none() {
+                        selection.pack = None;
+                        selection.page = 0;
+                    }
+                    commands.insert_resource(RebuildMenu);
+                }
+                MenuButton::Import(filename) => {
+                    // The song file is saved next to the chart, where its audio is
+                    let stem = Path::new(filename).with_extension("").display().to_string();
+                    match import_file(&Path::new("assets/songs").join(filename))
+                        .map_err(|err| err.to_string())
+                        .and_then(|config| save_map(&stem, &config).map_err(|err| err.to_string()))
+                    {
+                        Ok(saved) => info!("Imported {} into {}", filename, saved),
+                        Err(err) => error!("Couldn't import {}: {}", filename, err),
+                    }
+                    library.scan();
+                    commands.insert_resource(RebuildMenu);
+                }
+                MenuButton::Broken(_) => {}
+                MenuButton::KeyBindings => state
+                    .set(AppState::KeyBindings)
+                    .expect("Couldn't switch state to KeyBindings"),
+                MenuButton::Calibration => state
+                    .set(AppState::Calibration)
+                    .expect("Couldn't switch state to Calibration"),
+                MenuButton::Page { page, .. } => {
+                    selection.page = *page;
+                    commands.insert_resource(RebuildMenu);
+                }
+            }
+        }
+    }
+}
+
+/// Goes to the previous or next page with PageUp and PageDown, like their buttons
+fn turn_page(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    query: Query<&MenuButton>,
+    mut selection: ResMut<MenuSelection>,
+) {
+    let next = if keyboard_input.just_pressed(KeyCode::PageDown) {
+        true
+    } else if keyboard_input.just_pressed(KeyCode::PageUp) {
+        false
+    } else {
+        return;
+    };
+
+    for button in query.iter() {
+        if let MenuButton::Page {
+            page,
+            next: is_next,
+            ..
+        } = button
+        {
+            if *is_next == next {
+                selection.page = *page;
+                commands.insert_resource(RebuildMenu);
             }
         }
     }
 }
 
-fn get_songs_iter() -> impl Iterator<Item = String> {
-    read_dir("assets/songs")
-        .unwrap()
+/// Previews the song whose button is hovered, and stops when the mouse leaves it
+fn preview_hovered_song(
+    asset_server: Res<AssetServer>,
+    mut preview: ResMut<PreviewPlayer>,
+    query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+) {
+    let mut left = false;
+    for (interaction, button) in query.iter() {
+        let song = match button {
+            MenuButton::PlaySong(song) => song,
+            _ => continue,
+        };
+
+        // Moving from one song to another changes both in the same frame
+        match interaction {
+            Interaction::Hovered | Interaction::Clicked => {
+                let audio = song_file_path(&song.path, &song.filename);
+                preview.play(
+                    asset_server.load(&*format!("songs/{}", audio)),
+                    song.metadata.preview_start.unwrap_or(0.),
+                    song.metadata.preview_end,
+                );
+                return;
+            }
+            Interaction::None => left = true,
+        }
+    }
+
+    if left {
+        preview.stop();
+    }
+}
+
+/// Nudges the offset of the song whose button is hovered with the offset hotkeys.
+/// Only its text changes, rebuilding the menu would restart the preview
+fn nudge_hovered_offset(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    high_scores: Res<HighScores>,
+    mut song_offsets: ResMut<SongOffsets>,
+    buttons: Query<(&Interaction, &MenuButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let delta = match offset_nudge(&keyboard_input, &key_bindings) {
+        Some(delta) => delta,
+        None => return,
+    };
+
+    for (interaction, button, children) in buttons.iter() {
+        let song = match (interaction, button) {
+            (Interaction::Hovered, MenuButton::PlaySong(song)) => song,
+            _ => continue,
+        };
+
+        song_offsets.nudge(&song.path, delta);
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = button.name(&high_scores, &song_offsets);
+            }
+        }
+    }
+}
+
+/// Opens the `difficulty` chart of the song in `assets/songs/{path}` in the editor,
+/// or starts loading it to play it
+fn pick_chart(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    state: &mut State<AppState>,
+    path: &str,
+    difficulty: Difficulty,
+    edit: bool,
+) {
+    let path = path.to_string();
+    if edit {
+        commands.insert_resource(EditMap { path, difficulty });
+        state
+            .set(AppState::MakeMap)
+            .expect("Couldn't switch state to MakeMap")
+    } else {
+        commands.insert_resource(LoadingSong {
+            file: asset_server.load(&*format!("songs/{}", path)),
+            path,
+            difficulty,
+        });
+    }
+}
+
+/// Reads the files in `assets/songs`, logging instead of failing if it's missing
+fn read_songs_dir() -> impl Iterator<Item = PathBuf> {
+    let entries = read_dir("assets/songs")
+        .map_err(|err| error!("Couldn't read assets/songs: {}", err))
+        .ok();
+
+    entries
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+}
+
+/// Audio files in `assets/songs` that a map can be made for
+fn get_audio_iter() -> impl Iterator<Item = String> {
+    read_songs_dir()
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext))
+        })
+        .filter_map(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.to_string())
+        })
+}
+
+/// Charts in `assets/songs` and its folders that can be imported, and haven't been yet.
+/// Paths are relative to `assets/songs`
+fn get_importable_iter() -> impl Iterator<Item = String> {
+    walk_songs_dir()
         .into_iter()
-        .filter_map(|path| path.ok())
-        .map(|dir| dir.path())
-        .filter(|path| path.as_path().extension().unwrap() == "toml")
-        .map(|path| {
-            path.as_path()
-                .file_stem()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string()
+        .filter(|path| is_importable(path) && !path.with_extension("toml").exists())
+        .filter_map(|path| {
+            path.strip_prefix("assets/songs")
+                .ok()
+                .map(|path| path.display().to_string())
         })
 }
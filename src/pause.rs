@@ -0,0 +1,212 @@
+use crate::{
+    consts::*,
+    menu::{button_color_system, ButtonMaterials},
+};
+use bevy::prelude::*;
+
+pub struct PausePlugin;
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(SystemSet::on_update(AppState::Game).with_system(pause_song.system()))
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(setup_pause.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Paused)
+                    .with_system(button_color_system.system())
+                    .with_system(pause_button_system.system())
+                    .with_system(resume_countdown.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused).with_system(despawn_pause.system()),
+            );
+    }
+}
+
+fn pause_song(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<AppState>>) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // So the pause menu doesn't see the same press and resume right away
+        keyboard_input.reset(KeyCode::Escape);
+        state
+            .push(AppState::Paused)
+            .expect("Couldn't switch state to Paused");
+    }
+}
+
+struct PauseUI;
+
+/// Node holding the buttons, hidden during the countdown
+struct PauseButtons;
+
+struct CountdownText;
+
+/// Time left before the song resumes, only present while counting down
+struct ResumeCountdown(Timer);
+
+enum PauseButton {
+    Resume,
+    Restart,
+    Quit,
+}
+
+impl PauseButton {
+    fn name(&self) -> &'static str {
+        match self {
+            PauseButton::Resume => "Resume",
+            PauseButton::Restart => "Restart",
+            PauseButton::Quit => "Back to menu",
+        }
+    }
+}
+
+fn setup_pause(
+    mut commands: Commands,
+    button_materials: Res<ButtonMaterials>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_style = TextStyle {
+        font: button_materials.font.clone(),
+        font_size: 40.,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            // Dims the game behind the menu
+            material: color_materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+            ..Default::default()
+        })
+        .insert(PauseUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("Paused", text_style.clone(), Default::default()),
+                    ..Default::default()
+                })
+                .insert(CountdownText);
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: button_materials.none.clone(),
+                    ..Default::default()
+                })
+                .insert(PauseButtons)
+                .with_children(|parent| {
+                    for button in [PauseButton::Resume, PauseButton::Restart, PauseButton::Quit] {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(200.0), Val::Px(50.0)),
+                                    margin: Rect::all(Val::Px(10.)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                material: button_materials.normal.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        button.name(),
+                                        TextStyle {
+                                            font_size: 20.,
+                                            ..text_style.clone()
+                                        },
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            })
+                            .insert(button);
+                    }
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn pause_button_system(
+    mut commands: Commands,
+    query: Query<(&Interaction, &PauseButton), (Changed<Interaction>, With<Button>)>,
+    mut buttons: Query<&mut Style, With<PauseButtons>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    countdown: Option<Res<ResumeCountdown>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if countdown.is_some() {
+        return;
+    }
+
+    let mut pressed = query
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button);
+    let button = if keyboard_input.just_pressed(KeyCode::Escape) {
+        Some(&PauseButton::Resume)
+    } else {
+        pressed.next()
+    };
+
+    match button {
+        Some(PauseButton::Resume) => {
+            commands.insert_resource(ResumeCountdown(Timer::from_seconds(
+                RESUME_COUNTDOWN,
+                false,
+            )));
+            for mut style in buttons.iter_mut() {
+                style.display = Display::None;
+            }
+        }
+        // Replacing unwinds the paused game, which resets it
+        Some(PauseButton::Restart) => state
+            .replace(AppState::Game)
+            .expect("Couldn't restart the song"),
+        Some(PauseButton::Quit) => state
+            .replace(AppState::Menu)
+            .expect("Couldn't switch state to Menu"),
+        None => {}
+    }
+}
+
+fn resume_countdown(
+    mut commands: Commands,
+    time: Res<Time>,
+    countdown: Option<ResMut<ResumeCountdown>>,
+    mut query: Query<&mut Text, With<CountdownText>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let mut countdown = match countdown {
+        Some(countdown) => countdown,
+        None => return,
+    };
+
+    countdown.0.tick(time.delta());
+    if countdown.0.finished() {
+        commands.remove_resource::<ResumeCountdown>();
+        state.pop().expect("Couldn't resume the song");
+        return;
+    }
+
+    let secs_left = (countdown.0.duration() - countdown.0.elapsed()).as_secs_f32();
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("{}", secs_left.ceil());
+    }
+}
+
+fn despawn_pause(mut commands: Commands, query: Query<Entity, With<PauseUI>>) {
+    commands.remove_resource::<ResumeCountdown>();
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
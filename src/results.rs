@@ -0,0 +1,204 @@
+use crate::{
+    arrow::Arrow,
+    audio::SongPlayer,
+    clock::{SongClock, SongClockLabel},
+    consts::*,
+    menu::{button_color_system, ButtonMaterials},
+    score::{Judgment, ScoreResource},
+    types::SongConfig,
+};
+use bevy::prelude::*;
+
+pub struct ResultsPlugin;
+impl Plugin for ResultsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<LastScore>()
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(end_song.system().after(SongClockLabel)),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(take_score.system()))
+            .add_system_set(
+                SystemSet::on_enter(AppState::Results).with_system(setup_results.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Results)
+                    .with_system(button_color_system.system())
+                    .with_system(results_button_system.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Results).with_system(despawn_results.system()),
+            );
+    }
+}
+
+/// Score of the last song played, the [ScoreResource] is reset for the next one
+#[derive(Default)]
+pub struct LastScore(pub ScoreResource);
+
+fn take_score(mut score: ResMut<ScoreResource>, mut last_score: ResMut<LastScore>) {
+    last_score.0 = std::mem::take(&mut *score);
+}
+
+/// Goes to the results once every arrow has been judged and the song has finished
+fn end_song(
+    clock: Res<SongClock>,
+    song_config: Res<SongConfig>,
+    player: Res<SongPlayer>,
+    arrows: Query<(), With<Arrow>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    // Waits a frame after the start of the song, so its audio has been queued
+    if clock.seconds_last() <= 0.
+        || !song_config.arrows.is_empty()
+        || arrows.iter().next().is_some()
+        || player.is_playing()
+    {
+        return;
+    }
+
+    state
+        .set(AppState::Results)
+        .expect("Couldn't switch state to Results");
+}
+
+struct ResultsUI;
+
+enum ResultsButton {
+    Retry,
+    Menu,
+}
+
+impl ResultsButton {
+    fn name(&self) -> &'static str {
+        match self {
+            ResultsButton::Retry => "Retry",
+            ResultsButton::Menu => "Back to menu",
+        }
+    }
+}
+
+fn setup_results(
+    mut commands: Commands,
+    button_materials: Res<ButtonMaterials>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    last_score: Res<LastScore>,
+    song_config: Res<SongConfig>,
+) {
+    let score = &last_score.0;
+    let text_style = TextStyle {
+        font: button_materials.font.clone(),
+        font_size: 30.,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    let mut lines = vec![
+        format!("{} ({:?})", song_config.name, song_config.difficulty),
+        format!("Grade: {}", score.grade().name()),
+        format!("Score: {}", score.score()),
+        format!("Accuracy: {:.2}%", score.accuracy() * 100.),
+        format!("Max combo: {}", score.max_combo()),
+    ];
+    lines.extend(
+        Judgment::ALL
+            .iter()
+            .map(|judgment| format!("{}: {}", judgment.name(), score.judgment_count(*judgment))),
+    );
+    lines.push(format!(
+        "Holds: {}, Dropped: {}",
+        score.holds(),
+        score.hold_fails()
+    ));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            // Covers what's left of the game
+            material: color_materials.add(Color::rgb(0.05, 0.05, 0.08).into()),
+            ..Default::default()
+        })
+        .insert(ResultsUI)
+        .with_children(|parent| {
+            for line in lines {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(line, text_style.clone(), Default::default()),
+                    ..Default::default()
+                });
+            }
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        margin: Rect {
+                            top: Val::Px(20.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    material: button_materials.none.clone(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for button in [ResultsButton::Retry, ResultsButton::Menu] {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(200.0), Val::Px(50.0)),
+                                    margin: Rect::all(Val::Px(10.)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                material: button_materials.normal.clone(),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        button.name(),
+                                        TextStyle {
+                                            font_size: 20.,
+                                            ..text_style.clone()
+                                        },
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            })
+                            .insert(button);
+                    }
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn results_button_system(
+    query: Query<(&Interaction, &ResultsButton), (Changed<Interaction>, With<Button>)>,
+    mut state: ResMut<State<AppState>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction == Interaction::Clicked {
+            match button {
+                ResultsButton::Retry => state
+                    .set(AppState::Game)
+                    .expect("Couldn't switch state to Game"),
+                ResultsButton::Menu => state
+                    .set(AppState::Menu)
+                    .expect("Couldn't switch state to Menu"),
+            }
+        }
+    }
+}
+
+fn despawn_results(mut commands: Commands, query: Query<Entity, With<ResultsUI>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,26 +1,216 @@
 use crate::consts::*;
 
+/// How accurately an arrow was clicked
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Judgment {
+    Perfect,
+    Great,
+    Good,
+    Bad,
+    Miss,
+}
+
+impl Judgment {
+    pub const ALL: [Judgment; 5] = [
+        Judgment::Perfect,
+        Judgment::Great,
+        Judgment::Good,
+        Judgment::Bad,
+        Judgment::Miss,
+    ];
+
+    /// Judges a click `offset` milliseconds away from the click time of an arrow
+    pub fn from_offset(offset: f64) -> Self {
+        let offset = offset.abs();
+        if offset <= PERFECT_WINDOW {
+            Judgment::Perfect
+        } else if offset <= GREAT_WINDOW {
+            Judgment::Great
+        } else if offset <= GOOD_WINDOW {
+            Judgment::Good
+        } else if offset <= BAD_WINDOW {
+            Judgment::Bad
+        } else {
+            Judgment::Miss
+        }
+    }
+
+    pub fn points(&self) -> usize {
+        match self {
+            Judgment::Perfect => 100,
+            Judgment::Great => 80,
+            Judgment::Good => 50,
+            Judgment::Bad => 10,
+            Judgment::Miss => 0,
+        }
+    }
+
+    /// Whether getting this judgment resets the combo
+    pub fn breaks_combo(&self) -> bool {
+        matches!(self, Judgment::Bad | Judgment::Miss)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Judgment::Perfect => "Perfect",
+            Judgment::Great => "Great",
+            Judgment::Good => "Good",
+            Judgment::Bad => "Bad",
+            Judgment::Miss => "Miss",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Letter grade for a play, based on its accuracy
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Grade {
+    S,
+    A,
+    B,
+    C,
+    D,
+    F,
+}
+
+impl Grade {
+    /// Grade for an accuracy between 0. and 1.
+    pub fn from_accuracy(accuracy: f32) -> Self {
+        if accuracy >= 0.95 {
+            Grade::S
+        } else if accuracy >= 0.9 {
+            Grade::A
+        } else if accuracy >= 0.8 {
+            Grade::B
+        } else if accuracy >= 0.7 {
+            Grade::C
+        } else if accuracy >= 0.6 {
+            Grade::D
+        } else {
+            Grade::F
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+            Grade::F => "F",
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct ScoreResource {
     corrects: usize,
     fails: usize,
+    holds: usize,
+    hold_fails: usize,
     score: usize,
+    judgments: [usize; Judgment::ALL.len()],
+    last_judgment: Option<Judgment>,
+    combo: usize,
+    max_combo: usize,
 }
 
 impl ScoreResource {
-    pub fn increase_correct(&mut self, distance: f32) -> usize {
+    /// An arrow was clicked, returns the points it was worth
+    pub fn increase_correct(&mut self, judgment: Judgment) -> usize {
         self.corrects += 1;
+        self.judge(judgment);
 
-        let score_multiplier = (THRESHOLD - distance.abs()) / THRESHOLD;
-
-        let points = (score_multiplier * 100.).clamp(10., 100.) as usize;
+        let points = (judgment.points() as f32 * self.combo_multiplier()) as usize;
         self.score += points;
 
         points
     }
 
+    /// An arrow went by without being clicked
     pub fn increase_fails(&mut self) {
         self.fails += 1;
+        self.judge(Judgment::Miss);
+    }
+
+    /// A hold arrow was held until its end, returns the points it was worth
+    pub fn increase_hold_correct(&mut self) -> usize {
+        self.holds += 1;
+        self.increase_combo();
+
+        let points = (HOLD_POINTS as f32 * self.combo_multiplier()) as usize;
+        self.score += points;
+
+        points
+    }
+
+    /// A hold arrow was released too early
+    pub fn increase_hold_fails(&mut self) {
+        self.hold_fails += 1;
+        self.combo = 0;
+    }
+
+    fn judge(&mut self, judgment: Judgment) {
+        self.judgments[judgment.index()] += 1;
+        self.last_judgment = Some(judgment);
+
+        if judgment.breaks_combo() {
+            self.combo = 0;
+        } else {
+            self.increase_combo();
+        }
+    }
+
+    fn increase_combo(&mut self) {
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+    }
+
+    /// Points are multiplied by this, it grows every [COMBO_MULTIPLIER_STEP] arrows of combo
+    pub fn combo_multiplier(&self) -> f32 {
+        let steps = (self.combo / COMBO_MULTIPLIER_STEP) as f32;
+        (1. + steps * COMBO_MULTIPLIER_INCREMENT).min(MAX_COMBO_MULTIPLIER)
+    }
+
+    /// Points earned without combo multiplier over the most points possible, between 0. and 1.
+    pub fn accuracy(&self) -> f32 {
+        let judged: usize = self.judgments.iter().sum();
+        let max_points =
+            (judged * Judgment::Perfect.points()) + (self.holds + self.hold_fails) * HOLD_POINTS;
+        if max_points == 0 {
+            return 0.;
+        }
+
+        let points: usize = Judgment::ALL
+            .iter()
+            .map(|judgment| judgment.points() * self.judgment_count(*judgment))
+            .sum::<usize>()
+            + self.holds * HOLD_POINTS;
+        points as f32 / max_points as f32
+    }
+
+    pub fn grade(&self) -> Grade {
+        Grade::from_accuracy(self.accuracy())
+    }
+
+    pub fn judgment_count(&self, judgment: Judgment) -> usize {
+        self.judgments[judgment.index()]
+    }
+
+    pub fn last_judgment(&self) -> Option<Judgment> {
+        self.last_judgment
+    }
+
+    pub fn combo(&self) -> usize {
+        self.combo
+    }
+
+    pub fn max_combo(&self) -> usize {
+        self.max_combo
     }
 
     pub fn score(&self) -> usize {
@@ -34,4 +224,12 @@ impl ScoreResource {
     pub fn fails(&self) -> usize {
         self.fails
     }
+
+    pub fn holds(&self) -> usize {
+        self.holds
+    }
+
+    pub fn hold_fails(&self) -> usize {
+        self.hold_fails
+    }
 }
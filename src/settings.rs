@@ -0,0 +1,79 @@
+use crate::{
+    calibration::Calibration,
+    consts::*,
+    key_bindings::{GamepadBindings, KeyBindings},
+};
+use bevy::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let settings = Settings::load();
+        app.insert_resource(settings.key_bindings)
+            .insert_resource(settings.gamepad_bindings)
+            .insert_resource(settings.calibration)
+            .add_system(save_settings.system());
+    }
+}
+
+/// Player settings, saved in [SETTINGS_PATH]. Each part is its own resource while the game runs
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default)]
+struct Settings {
+    key_bindings: KeyBindings,
+    gamepad_bindings: GamepadBindings,
+    calibration: Calibration,
+}
+
+impl Settings {
+    fn load() -> Self {
+        let contents = match fs::read_to_string(SETTINGS_PATH) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match toml::from_str(&contents) {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("Couldn't parse {}, ignoring it: {}", SETTINGS_PATH, err);
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let result = toml::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = Path::new(SETTINGS_PATH).parent() {
+                    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+                }
+                fs::write(SETTINGS_PATH, contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            error!("Couldn't save settings: {}", err);
+        }
+    }
+}
+
+/// Saves the settings whenever one of them changes
+fn save_settings(
+    key_bindings: Res<KeyBindings>,
+    gamepad_bindings: Res<GamepadBindings>,
+    calibration: Res<Calibration>,
+) {
+    let changed = (key_bindings.is_changed() && !key_bindings.is_added())
+        || (gamepad_bindings.is_changed() && !gamepad_bindings.is_added())
+        || (calibration.is_changed() && !calibration.is_added());
+    if changed {
+        Settings {
+            key_bindings: key_bindings.clone(),
+            gamepad_bindings: gamepad_bindings.clone(),
+            calibration: calibration.clone(),
+        }
+        .save();
+    }
+}
@@ -48,3 +48,71 @@ pub fn update_background_size(
         }
     }
 }
+
+/// Background image of the song being played, shown instead of the [Background] shader
+pub struct SongBackground;
+
+pub fn spawn_song_background(
+    mut commands: Commands,
+    song_config: Res<SongConfig>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut background: Query<&mut Visible, With<Background>>,
+) {
+    let texture = match &song_config.background {
+        Some(texture) => texture.clone(),
+        None => return,
+    };
+
+    // Dimmed so the arrows stand out
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(ColorMaterial::modulated_texture(
+                texture,
+                Color::rgb(0.4, 0.4, 0.4),
+            )),
+            ..Default::default()
+        })
+        .insert(SongBackground);
+
+    for mut visible in background.iter_mut() {
+        visible.is_visible = false;
+    }
+}
+
+/// Scales the background image to cover the window, once it's loaded
+pub fn fit_song_background(
+    windows: Res<Windows>,
+    textures: Res<Assets<Texture>>,
+    song_config: Res<SongConfig>,
+    mut query: Query<&mut Transform, With<SongBackground>>,
+) {
+    let (window, texture) = match (
+        windows.get_primary(),
+        song_config
+            .background
+            .as_ref()
+            .and_then(|texture| textures.get(texture)),
+    ) {
+        (Some(window), Some(texture)) => (window, texture),
+        _ => return,
+    };
+
+    let scale = (window.width() / texture.size.width as f32)
+        .max(window.height() / texture.size.height as f32);
+    for mut transform in query.iter_mut() {
+        transform.scale = Vec3::new(scale, scale, 1.);
+    }
+}
+
+pub fn despawn_song_background(
+    mut commands: Commands,
+    query: Query<Entity, With<SongBackground>>,
+    mut background: Query<&mut Visible, With<Background>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for mut visible in background.iter_mut() {
+        visible.is_visible = true;
+    }
+}
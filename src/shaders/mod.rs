@@ -1,3 +1,4 @@
+use crate::{consts::AppState, types::SongConfig};
 use bevy::render::render_graph::RenderResourcesNode;
 use bevy::{
     prelude::*,
@@ -26,7 +27,16 @@ impl Plugin for ShadersPlugin {
             .add_system(update_resolution.system())
             .add_system(update_time.system())
             .add_startup_system(setup_target_arrows.system())
-            .add_system(correct_arrow_event_listener.system());
+            .add_system(correct_arrow_event_listener.system())
+            .add_system_set(
+                SystemSet::on_enter(AppState::Game).with_system(spawn_song_background.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Game).with_system(fit_song_background.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Game).with_system(despawn_song_background.system()),
+            );
     }
 }
 
@@ -75,7 +75,8 @@ pub fn correct_arrow_event_listener(
         for (arrow, mut last_correct) in query.iter_mut() {
             if arrow.direction == event.direction {
                 last_correct.last_time = time.seconds_since_startup() as f32;
-                last_correct.points = event.points as f32 / 100.;
+                // Better judgments make brighter sparkles
+                last_correct.points = event.judgment.points() as f32 / 100.;
             }
         }
     }
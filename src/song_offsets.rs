@@ -0,0 +1,123 @@
+use crate::{
+    arrow::Arrow,
+    consts::*,
+    key_bindings::{KeyBindings, LANES},
+    types::SongConfig,
+};
+use bevy::prelude::*;
+use std::{collections::BTreeMap, fs, path::Path};
+
+pub struct SongOffsetsPlugin;
+impl Plugin for SongOffsetsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(SongOffsets::load()).add_system_set(
+            SystemSet::on_update(AppState::Game).with_system(nudge_song_offset.system()),
+        );
+    }
+}
+
+/// Offsets of songs whose audio is off from their charts, like audio files with encoding delay.
+/// Applied on top of the [crate::calibration::Calibration], saved in [SONG_OFFSETS_PATH]
+#[derive(Default)]
+pub struct SongOffsets {
+    /// Milliseconds the arrows are moved later by, keyed by the path of the song file.
+    /// Unlike high scores they aren't keyed by the chart's contents, so editing it keeps them
+    offsets: BTreeMap<String, f64>,
+}
+
+impl SongOffsets {
+    fn load() -> Self {
+        let contents = match fs::read_to_string(SONG_OFFSETS_PATH) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match toml::from_str(&contents) {
+            Ok(offsets) => Self { offsets },
+            Err(err) => {
+                warn!("Couldn't parse {}, ignoring it: {}", SONG_OFFSETS_PATH, err);
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let result = toml::to_string(&self.offsets)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = Path::new(SONG_OFFSETS_PATH).parent() {
+                    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+                }
+                fs::write(SONG_OFFSETS_PATH, contents).map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            error!("Couldn't save song offsets: {}", err);
+        }
+    }
+
+    /// Offset in milliseconds of the song in `assets/songs/{path}`
+    pub fn get(&self, path: &str) -> f64 {
+        self.offsets.get(path).copied().unwrap_or(0.)
+    }
+
+    /// Offset in seconds of the song in `assets/songs/{path}`
+    pub fn secs(&self, path: &str) -> f64 {
+        self.get(path) / 1000.
+    }
+
+    /// Adds `delta` milliseconds to the offset of the song in `assets/songs/{path}`
+    pub fn nudge(&mut self, path: &str, delta: f64) {
+        let offset = self.get(path) + delta;
+        if offset == 0. {
+            self.offsets.remove(path);
+        } else {
+            self.offsets.insert(path.to_string(), offset);
+        }
+        self.save();
+    }
+}
+
+/// Milliseconds the offset hotkeys add, Minus moves the arrows earlier and Equals later.
+/// Hotkeys bound to a lane play that lane instead
+pub fn offset_nudge(keyboard_input: &Input<KeyCode>, key_bindings: &KeyBindings) -> Option<f64> {
+    let pressed = |key| {
+        keyboard_input.just_pressed(key)
+            && !LANES
+                .iter()
+                .any(|direction| key_bindings.keys(*direction).contains(&key))
+    };
+
+    if pressed(KeyCode::Minus) {
+        Some(-SONG_OFFSET_STEP)
+    } else if pressed(KeyCode::Equals) {
+        Some(SONG_OFFSET_STEP)
+    } else {
+        None
+    }
+}
+
+/// Nudges the offset of the song being played, moving the arrows it already has
+fn nudge_song_offset(
+    keyboard_input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut song_offsets: ResMut<SongOffsets>,
+    mut song_config: ResMut<SongConfig>,
+    mut query: Query<(&mut Arrow, &mut Transform)>,
+) {
+    let delta = match offset_nudge(&keyboard_input, &key_bindings) {
+        Some(delta) => delta,
+        None => return,
+    };
+    song_offsets.nudge(&song_config.path, delta);
+
+    let secs = delta / 1000.;
+    song_config.offset += secs;
+    for arrow in song_config.arrows.iter_mut() {
+        arrow.click_time += secs;
+        arrow.spawn_time += secs;
+    }
+    for (mut arrow, mut transform) in query.iter_mut() {
+        arrow.delay(secs, &mut transform);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    consts::DEFAULT_BPM,
+    types::{BpmChange, SongConfigToml, TimeSignature},
+};
+
+/// Converts between beats and seconds of song time, from the tempo of a song.
+/// Beats are quarter notes, whatever the time signature
+#[derive(Debug, Clone)]
+pub struct TempoMap {
+    /// Tempo changes sorted by beat, starting with the tempo at beat 0
+    segments: Vec<Segment>,
+    /// Time signature changes sorted by beat, starting at beat 0
+    signatures: Vec<TimeSignature>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    beat: f64,
+    secs: f64,
+    bpm: f64,
+}
+
+impl TempoMap {
+    /// Tempo map starting at `offset` seconds with `bpm`, changing at each of `bpm_changes`
+    pub fn new(
+        offset: f64,
+        bpm: f64,
+        bpm_changes: &[BpmChange],
+        time_signatures: &[TimeSignature],
+    ) -> Self {
+        let mut changes = bpm_changes.to_vec();
+        changes.sort_by(|a, b| a.beat.partial_cmp(&b.beat).unwrap());
+
+        let mut segments = vec![Segment {
+            beat: 0.,
+            secs: offset,
+            bpm,
+        }];
+        for change in changes.iter().filter(|change| change.beat > 0.) {
+            let last = segments.last().unwrap();
+            segments.push(Segment {
+                beat: change.beat,
+                secs: last.secs + (change.beat - last.beat) * 60. / last.bpm,
+                bpm: change.bpm,
+            });
+        }
+
+        let mut signatures = time_signatures.to_vec();
+        signatures.sort_by(|a, b| a.beat.partial_cmp(&b.beat).unwrap());
+        if signatures
+            .first()
+            .is_none_or(|signature| signature.beat > 0.)
+        {
+            signatures.insert(0, TimeSignature::default());
+        }
+
+        Self {
+            segments,
+            signatures,
+        }
+    }
+
+    /// Tempo segment `beat` is in, the first one for beats before 0
+    fn segment_at_beat(&self, beat: f64) -> &Segment {
+        self.segments
+            .iter()
+            .rev()
+            .find(|segment| segment.beat <= beat)
+            .unwrap_or(&self.segments[0])
+    }
+
+    /// Seconds of song time at `beat`
+    pub fn seconds(&self, beat: f64) -> f64 {
+        let segment = self.segment_at_beat(beat);
+        segment.secs + (beat - segment.beat) * 60. / segment.bpm
+    }
+
+    /// Beat at `secs` seconds of song time
+    pub fn beat(&self, secs: f64) -> f64 {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|segment| segment.secs <= secs)
+            .unwrap_or(&self.segments[0]);
+        segment.beat + (secs - segment.secs) * segment.bpm / 60.
+    }
+
+    /// Beats per minute at `beat`
+    pub fn bpm(&self, beat: f64) -> f64 {
+        self.segment_at_beat(beat).bpm
+    }
+
+    fn signature_at(&self, beat: f64) -> &TimeSignature {
+        self.signatures
+            .iter()
+            .rev()
+            .find(|signature| signature.beat <= beat)
+            .unwrap_or(&self.signatures[0])
+    }
+
+    /// Time signature at `beat`
+    pub fn time_signature(&self, beat: f64) -> (u32, u32) {
+        let signature = self.signature_at(beat);
+        (signature.numerator, signature.denominator)
+    }
+
+    /// Beats in the measure at `beat`
+    pub fn measure_length(&self, beat: f64) -> f64 {
+        self.signature_at(beat).measure_length()
+    }
+
+    /// Checks if a measure starts at `beat`
+    pub fn is_measure_start(&self, beat: f64) -> bool {
+        let signature = self.signature_at(beat);
+        let measures = (beat - signature.beat) / signature.measure_length();
+        (measures - measures.round()).abs() < 1e-6
+    }
+}
+
+impl SongConfigToml {
+    pub fn tempo_map(&self) -> TempoMap {
+        TempoMap::new(
+            self.offset,
+            self.bpm.unwrap_or(DEFAULT_BPM),
+            &self.bpm_changes,
+            &self.time_signatures,
+        )
+    }
+}
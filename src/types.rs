@@ -1,16 +1,20 @@
 use bevy::{
     audio::AudioSource,
-    input::{keyboard::KeyCode, Input},
-    prelude::{AssetServer, Handle},
+    prelude::{AssetServer, Handle, Texture},
+    reflect::TypeUuid,
+};
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
 };
-use serde::{Deserialize, Serialize};
 
-use crate::consts::*;
+use crate::{consts::*, tempo::TempoMap};
 
 use core::f32::consts::PI;
-use std::{fs::File, io::Read, path::Path};
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+use toml::Spanned;
 
-#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Directions {
     Up,
     Down,
@@ -19,18 +23,6 @@ pub enum Directions {
 }
 
 impl Directions {
-    /// Checks if a key that corresponds to this direction has been pressed
-    pub fn key_just_pressed(&self, input: &Input<KeyCode>) -> bool {
-        let keys = match self {
-            Directions::Up => [KeyCode::Up, KeyCode::W],
-            Directions::Down => [KeyCode::Down, KeyCode::S],
-            Directions::Left => [KeyCode::Left, KeyCode::A],
-            Directions::Right => [KeyCode::Right, KeyCode::D],
-        };
-
-        keys.iter().any(|code| input.just_pressed(*code))
-    }
-
     /// Returns the correct rotation for an arrow with this direction
     pub fn rotation(&self) -> f32 {
         match self {
@@ -76,6 +68,9 @@ impl Speed {
 #[derive(Clone, Copy, Debug)]
 pub struct ArrowTime {
     pub spawn_time: f64,
+    pub click_time: f64,
+    /// Seconds the key has to be held after clicking, only for hold arrows
+    pub duration: Option<f64>,
     pub speed: Speed,
     pub direction: Directions,
 }
@@ -85,12 +80,19 @@ impl ArrowTime {
         ArrowTimeToml {
             speed,
             click_time,
+            beat,
             direction,
+            duration,
         }: &ArrowTimeToml,
+        tempo: &TempoMap,
+        offset: f64,
     ) -> Self {
         let speed_value = speed.value();
+        let click_time = beat.map_or(*click_time, |beat| tempo.seconds(beat)) + offset;
         Self {
             spawn_time: click_time - (DISTANCE / speed_value) as f64,
+            click_time,
+            duration: duration.filter(|duration| *duration > 0.),
             speed: *speed,
             direction: *direction,
         }
@@ -99,50 +101,828 @@ impl ArrowTime {
 
 #[derive(Debug)]
 pub struct SongConfig {
+    /// File the config was loaded from, relative to `assets/songs`
+    pub path: String,
+    /// Song file asset the config was built from, reloaded when the file changes
+    pub file: Handle<SongConfigToml>,
+    pub difficulty: Difficulty,
+    pub rating: u32,
     pub name: String,
+    pub metadata: SongMetadata,
     pub song_audio: Handle<AudioSource>,
+    /// Image from [SongMetadata::background]
+    pub background: Option<Handle<Texture>>,
+    /// Seconds the arrows are moved later by, from [crate::song_offsets::SongOffsets]
+    pub offset: f64,
     pub arrows: Vec<ArrowTime>,
 }
 
-pub fn load_config<P: AsRef<Path> + std::fmt::Display>(
-    path: P,
-    asset_server: &AssetServer,
-) -> SongConfig {
-    let mut file = File::open(format!("assets/songs/{}", path)).expect("Couldn't open file");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .expect("Couldn't read file into String");
+impl SongConfig {
+    /// Builds the config of the `difficulty` chart of the song in `assets/songs/{path}`,
+    /// once its asset is loaded. Its arrows are `offset` seconds late
+    pub fn new(
+        path: String,
+        file: Handle<SongConfigToml>,
+        parsed: &SongConfigToml,
+        difficulty: Difficulty,
+        offset: f64,
+        asset_server: &AssetServer,
+    ) -> Self {
+        let asset_path = |file: &str| format!("songs/{}", song_file_path(&path, file));
+        let song_audio = asset_server.load(&*asset_path(&parsed.filename));
+        let background = parsed
+            .metadata
+            .background
+            .as_deref()
+            .map(|background| asset_server.load(&*asset_path(background)));
 
-    let parsed: SongConfigToml =
-        toml::from_str(&contents).expect("Couldn't parse into SOngConfigToml");
+        SongConfig {
+            path,
+            file,
+            difficulty,
+            rating: parsed.chart(difficulty).map_or(0, |chart| chart.rating),
+            name: parsed.name.clone(),
+            metadata: parsed.metadata.clone(),
+            song_audio,
+            background,
+            offset,
+            arrows: load_arrows(parsed, difficulty, offset),
+        }
+    }
+}
 
+/// Arrows of the `difficulty` chart of a song, `offset` seconds late, sorted by spawn time
+pub fn load_arrows(parsed: &SongConfigToml, difficulty: Difficulty, offset: f64) -> Vec<ArrowTime> {
+    let tempo = parsed.tempo_map();
     let mut arrows: Vec<_> = parsed
-        .arrows
+        .chart(difficulty)
+        .map(|chart| chart.arrows.as_slice())
+        .unwrap_or_default()
         .iter()
-        .map(|arr| ArrowTime::new(arr))
+        .map(|arrow| ArrowTime::new(arrow, &tempo, offset))
         .collect();
 
     arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
 
-    let song_audio = asset_server.load(&*format!("songs/{}", parsed.filename));
+    arrows
+}
+
+/// Path of a file next to the song file in `assets/songs/{path}`, relative to `assets/songs`.
+/// Audio and images of songs are relative to the folder of their song file
+pub fn song_file_path(path: &str, file: &str) -> String {
+    Path::new(path).with_file_name(file).display().to_string()
+}
+
+/// Reads a song file in `assets/songs` without building a [SongConfig] from it
+pub fn load_config_toml<P: AsRef<Path> + std::fmt::Display>(
+    path: P,
+) -> Result<SongConfigToml, ChartError> {
+    let contents =
+        fs::read_to_string(format!("assets/songs/{}", path)).map_err(|source| ChartError::Io {
+            path: path.to_string(),
+            source,
+        })?;
+
+    parse_config_toml(path, &contents)
+}
+
+/// Parses and checks the contents of the song file in `assets/songs/{path}`
+pub fn parse_config_toml<P: std::fmt::Display>(
+    path: P,
+    contents: &str,
+) -> Result<SongConfigToml, ChartError> {
+    let mut config: SongConfigToml =
+        toml::from_str(contents).map_err(|source| ChartError::Parse {
+            path: path.to_string(),
+            source,
+        })?;
+
+    config
+        .move_arrows_into_chart()
+        .and_then(|_| config.validate(&path.to_string()))
+        .map_err(|(field, message)| ChartError::Invalid {
+            path: path.to_string(),
+            line: field_line(contents, &field),
+            field,
+            message,
+        })?;
+
+    Ok(config)
+}
+
+/// Song file parsed only for where its keys are, to point errors at their line
+enum Located {
+    Table(BTreeMap<Spanned<String>, Located>),
+    Array(Vec<Located>),
+    Value,
+}
+
+impl<'de> Deserialize<'de> for Located {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LocatedVisitor)
+    }
+}
+
+struct LocatedVisitor;
+
+impl<'de> Visitor<'de> for LocatedVisitor {
+    type Value = Located;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a TOML value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Located, A::Error> {
+        let mut table = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            table.insert(key, value);
+        }
+        Ok(Located::Table(table))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Located, A::Error> {
+        let mut array = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            array.push(value);
+        }
+        Ok(Located::Array(array))
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Located, E> {
+        Ok(Located::Value)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Located, E> {
+        Ok(Located::Value)
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Located, E> {
+        Ok(Located::Value)
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Located, E> {
+        Ok(Located::Value)
+    }
+}
+
+/// Line of `field` in the song file `contents`, with fields named like `charts[1].arrows[3].beat`.
+/// When only the start of `field` is found, gives the line of that part
+fn field_line(contents: &str, field: &str) -> Option<usize> {
+    let line_of = |offset: usize| contents[..offset].matches('\n').count() + 1;
+    let mut value: &Located = &toml::from_str(contents).ok()?;
+    let mut line = None;
+
+    for segment in field.split('.') {
+        let (key, index) = match segment.split_once('[') {
+            Some((key, index)) => (key, index.trim_end_matches(']').parse::<usize>().ok()),
+            None => (segment, None),
+        };
+
+        let table = match value {
+            Located::Table(table) => table,
+            _ => break,
+        };
+        match table.get_key_value(key) {
+            Some((key, inner)) => {
+                line = Some(line_of(key.start()));
+                value = inner;
+            }
+            // The arrows of songs with a single chart are written outside of charts
+            None if key == "charts" && index == Some(0) => continue,
+            None => break,
+        }
 
-    SongConfig {
-        name: parsed.name,
-        song_audio,
-        arrows,
+        if let Some(index) = index {
+            match value {
+                Located::Array(array) if index < array.len() => value = &array[index],
+                _ => break,
+            }
+            // An element is on the line of its first key
+            if let Located::Table(table) = value {
+                line = table
+                    .keys()
+                    .map(|key| key.start())
+                    .min()
+                    .map(line_of)
+                    .or(line);
+            }
+        }
     }
+
+    line
+}
+
+/// Quoted and escaped TOML string
+fn toml_string(string: &str) -> String {
+    toml::Value::String(string.to_string()).to_string()
+}
+
+/// Shortest TOML float that parses back to `float`
+fn toml_float(float: f64) -> String {
+    // Debug formatting always keeps a fractional part or an exponent, so it reads back as a float.
+    // Adding 0. turns -0. into 0.
+    format!("{:?}", float + 0.)
 }
 
-#[derive(Deserialize, Debug)]
+/// Why a song file couldn't be loaded
+#[derive(Debug)]
+pub enum ChartError {
+    /// The file couldn't be read
+    Io { path: String, source: io::Error },
+    /// The file isn't valid TOML, or doesn't match [SongConfigToml]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+    /// The file parsed, but `field` has a value that can't be played
+    Invalid {
+        path: String,
+        /// Line `field` is on, if it could be found
+        line: Option<usize>,
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChartError::Io { path, source } => {
+                write!(f, "{}: couldn't read file: {}", path, source)
+            }
+            // The TOML error already mentions the line and column
+            ChartError::Parse { path, source } => write!(f, "{}: {}", path, source),
+            ChartError::Invalid {
+                path,
+                line: Some(line),
+                field,
+                message,
+            } => write!(f, "{}:{}: `{}` {}", path, line, field, message),
+            ChartError::Invalid {
+                path,
+                line: None,
+                field,
+                message,
+            } => write!(f, "{}: `{}` {}", path, field, message),
+        }
+    }
+}
+
+impl std::error::Error for ChartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChartError::Io { source, .. } => Some(source),
+            ChartError::Parse { source, .. } => Some(source),
+            ChartError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, TypeUuid)]
+#[uuid = "cd2a2102-e995-462a-98cd-896addc52853"]
 pub struct SongConfigToml {
     pub name: String,
+    #[serde(flatten)]
+    pub metadata: SongMetadata,
+    /// Audio file, relative to the folder of the song file like the images of the metadata
     pub filename: String,
+    /// Seconds between the start of the audio and beat 0
+    #[serde(default)]
+    pub offset: f64,
+    /// Tempo of the song at beat 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bpm_changes: Vec<BpmChange>,
+    /// 4/4 is assumed until the first time signature
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_signatures: Vec<TimeSignature>,
+    /// Arrows of songs with a single chart. Moved into [SongConfigToml::charts] when parsed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub arrows: Vec<ArrowTimeToml>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub charts: Vec<ChartToml>,
+}
+
+/// Optional details of a song shown in the menu, shared by all its charts
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SongMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// Who made the charts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charter: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Length of the song in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<f64>,
+    /// Seconds of the audio where the preview played in the menu starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview_start: Option<f64>,
+    /// Seconds of the audio where the preview ends, [PREVIEW_LENGTH] after its start if it's missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview_end: Option<f64>,
+    /// Image shown next to the song in the menu
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    /// Image shown behind the arrows while playing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+}
+
+/// How hard a chart is, a song has at most one chart of each
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Normal,
+    Hard,
+    Expert,
+}
+
+/// Arrows of one difficulty of a song
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ChartToml {
+    pub difficulty: Difficulty,
+    /// How hard the chart is compared to others of the same difficulty, higher is harder
+    #[serde(default)]
+    pub rating: u32,
+    pub arrows: Vec<ArrowTimeToml>,
+}
+
+impl SongConfigToml {
+    pub fn chart(&self, difficulty: Difficulty) -> Option<&ChartToml> {
+        self.charts
+            .iter()
+            .find(|chart| chart.difficulty == difficulty)
+    }
+
+    /// Turns the top level arrows of a single chart song into a [Difficulty::Normal] chart
+    fn move_arrows_into_chart(&mut self) -> Result<(), (String, String)> {
+        if self.charts.is_empty() {
+            self.charts.push(ChartToml {
+                difficulty: Difficulty::Normal,
+                rating: 0,
+                arrows: std::mem::take(&mut self.arrows),
+            });
+        } else if !self.arrows.is_empty() {
+            return Err((
+                "arrows".to_string(),
+                "can't be used along with charts, move them into a chart".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the chart in a canonical form, so saving the same chart always gives the same file.
+    /// Arrows are sorted by time, one per line, and numbers are written with as few digits as
+    /// reads back exactly
+    pub fn to_toml(&self) -> String {
+        let metadata = &self.metadata;
+        let mut out = String::new();
+        out += &format!("name = {}\n", toml_string(&self.name));
+        for (key, value) in [
+            ("artist", &metadata.artist),
+            ("charter", &metadata.charter),
+            ("genre", &metadata.genre),
+        ] {
+            if let Some(value) = value {
+                out += &format!("{} = {}\n", key, toml_string(value));
+            }
+        }
+        out += &format!("filename = {}\n", toml_string(&self.filename));
+        for (key, value) in [
+            ("length", metadata.length),
+            ("preview_start", metadata.preview_start),
+            ("preview_end", metadata.preview_end),
+        ] {
+            if let Some(value) = value {
+                out += &format!("{} = {}\n", key, toml_float(value));
+            }
+        }
+        for (key, value) in [
+            ("banner", &metadata.banner),
+            ("background", &metadata.background),
+        ] {
+            if let Some(value) = value {
+                out += &format!("{} = {}\n", key, toml_string(value));
+            }
+        }
+        if self.offset != 0. {
+            out += &format!("offset = {}\n", toml_float(self.offset));
+        }
+        if let Some(bpm) = self.bpm {
+            out += &format!("bpm = {}\n", toml_float(bpm));
+        }
+
+        if !self.bpm_changes.is_empty() {
+            let mut changes = self.bpm_changes.clone();
+            changes.sort_by(|a, b| a.beat.partial_cmp(&b.beat).unwrap());
+            out += "\nbpm_changes = [\n";
+            for change in changes {
+                out += &format!(
+                    "    {{ beat = {}, bpm = {} }},\n",
+                    toml_float(change.beat),
+                    toml_float(change.bpm)
+                );
+            }
+            out += "]\n";
+        }
+
+        if !self.time_signatures.is_empty() {
+            let mut signatures = self.time_signatures.clone();
+            signatures.sort_by(|a, b| a.beat.partial_cmp(&b.beat).unwrap());
+            out += "\ntime_signatures = [\n";
+            for signature in signatures {
+                out += &format!(
+                    "    {{ beat = {}, numerator = {}, denominator = {} }},\n",
+                    toml_float(signature.beat),
+                    signature.numerator,
+                    signature.denominator
+                );
+            }
+            out += "]\n";
+        }
+
+        let tempo = self.tempo_map();
+        // Songs with a single unrated Normal chart keep the simpler top level arrows
+        if let [chart] = &self.charts[..] {
+            if chart.difficulty == Difficulty::Normal && chart.rating == 0 {
+                out += "\n";
+                out += &write_arrows(&chart.arrows, &tempo);
+                return out;
+            }
+        }
+
+        let mut charts: Vec<_> = self.charts.iter().collect();
+        charts.sort_by_key(|chart| chart.difficulty);
+        for chart in charts {
+            out += "\n[[charts]]\n";
+            out += &format!("difficulty = \"{:?}\"\n", chart.difficulty);
+            out += &format!("rating = {}\n", chart.rating);
+            out += &write_arrows(&chart.arrows, &tempo);
+        }
+
+        out
+    }
+
+    /// Checks the values serde can't, returns the name of the bad field and what's wrong with it
+    fn validate(&self, path: &str) -> Result<(), (String, String)> {
+        let audio_path = song_file_path(path, &self.filename);
+        if !Path::new("assets/songs").join(&audio_path).is_file() {
+            return Err((
+                "filename".to_string(),
+                format!("is not an audio file in assets/songs: {}", audio_path),
+            ));
+        }
+
+        let metadata = &self.metadata;
+        for (field, value) in [
+            ("length", metadata.length),
+            ("preview_start", metadata.preview_start),
+            ("preview_end", metadata.preview_end),
+        ] {
+            if value.is_some_and(|value| !(value.is_finite() && value >= 0.)) {
+                return Err((
+                    field.to_string(),
+                    "must be a positive number of seconds".to_string(),
+                ));
+            }
+        }
+        if let (Some(start), Some(end)) = (metadata.preview_start, metadata.preview_end) {
+            if end <= start {
+                return Err((
+                    "preview_end".to_string(),
+                    "must be after preview_start".to_string(),
+                ));
+            }
+        }
+
+        if !self.offset.is_finite() {
+            return Err((
+                "offset".to_string(),
+                "must be a number of seconds".to_string(),
+            ));
+        }
+
+        if let Some(bpm) = self.bpm {
+            if !(bpm.is_finite() && bpm > 0.) {
+                return Err(("bpm".to_string(), "must be a positive number".to_string()));
+            }
+        }
+
+        for (i, change) in self.bpm_changes.iter().enumerate() {
+            if !(change.beat.is_finite() && change.beat >= 0.) {
+                return Err((
+                    format!("bpm_changes[{}].beat", i),
+                    "must be a positive number of beats".to_string(),
+                ));
+            }
+            if !(change.bpm.is_finite() && change.bpm > 0.) {
+                return Err((
+                    format!("bpm_changes[{}].bpm", i),
+                    "must be a positive number".to_string(),
+                ));
+            }
+        }
+
+        for (i, signature) in self.time_signatures.iter().enumerate() {
+            if !(signature.beat.is_finite() && signature.beat >= 0.) {
+                return Err((
+                    format!("time_signatures[{}].beat", i),
+                    "must be a positive number of beats".to_string(),
+                ));
+            }
+            if signature.numerator == 0 || signature.denominator == 0 {
+                return Err((
+                    format!("time_signatures[{}]", i),
+                    "can't have a 0 numerator or denominator".to_string(),
+                ));
+            }
+        }
+
+        if self.charts.is_empty() {
+            return Err((
+                "charts".to_string(),
+                "must have at least one chart".to_string(),
+            ));
+        }
+
+        let tempo = self.tempo_map();
+        for (c, chart) in self.charts.iter().enumerate() {
+            if self.charts[..c]
+                .iter()
+                .any(|other| other.difficulty == chart.difficulty)
+            {
+                return Err((
+                    format!("charts[{}].difficulty", c),
+                    format!("there's already a {:?} chart", chart.difficulty),
+                ));
+            }
+
+            for (i, arrow) in chart.arrows.iter().enumerate() {
+                if arrow.beat.is_some() && arrow.click_time != 0. {
+                    return Err((
+                        format!("charts[{}].arrows[{}]", c, i),
+                        "can't have both a click_time and a beat".to_string(),
+                    ));
+                }
+                if arrow.beat.is_some_and(|beat| !beat.is_finite()) {
+                    return Err((
+                        format!("charts[{}].arrows[{}].beat", c, i),
+                        "must be a number of beats".to_string(),
+                    ));
+                }
+                let secs = arrow.seconds(&tempo);
+                if !secs.is_finite() || secs < 0. {
+                    return Err((
+                        format!(
+                            "charts[{}].arrows[{}].{}",
+                            c,
+                            i,
+                            if arrow.beat.is_some() {
+                                "beat"
+                            } else {
+                                "click_time"
+                            }
+                        ),
+                        "must be at a positive number of seconds".to_string(),
+                    ));
+                }
+                if arrow.duration.is_some_and(|duration| !duration.is_finite()) {
+                    return Err((
+                        format!("charts[{}].arrows[{}].duration", c, i),
+                        "must be a number of seconds".to_string(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Inline `arrows = [...]` array of a chart, sorted by time then direction
+fn write_arrows(arrows: &[ArrowTimeToml], tempo: &TempoMap) -> String {
+    let mut arrows: Vec<_> = arrows
+        .iter()
+        .map(|arrow| (arrow.seconds(tempo), arrow))
+        .collect();
+    arrows.sort_by(|(a_secs, a), (b_secs, b)| {
+        a_secs
+            .partial_cmp(b_secs)
+            .unwrap()
+            .then((a.direction as u8).cmp(&(b.direction as u8)))
+    });
+
+    let mut out = "arrows = [\n".to_string();
+    for (_, arrow) in arrows {
+        let time = match arrow.beat {
+            Some(beat) => format!("beat = {}", toml_float(beat)),
+            None => format!("click_time = {}", toml_float(arrow.click_time)),
+        };
+        out += &format!(
+            "    {{ {}, speed = \"{:?}\", direction = \"{:?}\"",
+            time, arrow.speed, arrow.direction
+        );
+        if let Some(duration) = arrow.duration {
+            out += &format!(", duration = {}", toml_float(duration));
+        }
+        out += " },\n";
+    }
+    out += "]\n";
+
+    out
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ArrowTimeToml {
+    /// Seconds since the start of the audio, unless `beat` is set
+    #[serde(default)]
     pub click_time: f64,
+    /// Beat of the tempo map the arrow is at, replaces `click_time`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beat: Option<f64>,
     pub speed: Speed,
     pub direction: Directions,
+    /// Makes this a hold arrow, that has to be held for this many seconds after clicking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+}
+
+impl ArrowTimeToml {
+    /// Seconds since the start of the audio at which the arrow is clicked
+    pub fn seconds(&self, tempo: &TempoMap) -> f64 {
+        self.beat
+            .map_or(self.click_time, |beat| tempo.seconds(beat))
+    }
+
+    /// Moves the arrow to `beat` of `tempo`, keeping it in beats if it was written in beats
+    pub fn set_beat(&mut self, beat: f64, tempo: &TempoMap) {
+        match self.beat {
+            Some(_) => self.beat = Some(beat),
+            None => self.click_time = tempo.seconds(beat),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct BpmChange {
+    pub beat: f64,
+    pub bpm: f64,
+}
+
+/// Time signature starting at `beat`, which should be the start of a measure
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct TimeSignature {
+    pub beat: f64,
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    /// Beats in a measure, counting quarter notes
+    pub fn measure_length(&self) -> f64 {
+        self.numerator as f64 * 4. / self.denominator as f64
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            beat: 0.,
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a song file without checking that its audio file exists
+    fn parse(contents: &str) -> SongConfigToml {
+        let mut config: SongConfigToml = toml::from_str(contents).unwrap();
+        config.move_arrows_into_chart().unwrap();
+        config
+    }
+
+    const SONG: &str = r#"
+name = "Test \"song\""
+filename = "song.ogg"
+artist = "Someone"
+genre = "Pop"
+preview_start = 12.5
+preview_end = 27.1
+offset = -0.013
+bpm = 128
+bpm_changes = [{ beat = 64, bpm = 64.5 }]
+time_signatures = [{ beat = 0, numerator = 3, denominator = 4 }]
+
+[[charts]]
+difficulty = "Hard"
+rating = 7
+arrows = [
+    { click_time = 1.1, speed = "Fast", direction = "Up", duration = 0.3 },
+    { click_time = 0.1, speed = "Slow", direction = "Left" },
+]
+
+[[charts]]
+difficulty = "Easy"
+arrows = [
+    { beat = 8.25, speed = "Medium", direction = "Down" },
+    { beat = 1e-7, speed = "Medium", direction = "Right" },
+]
+"#;
+
+    const CANONICAL_SONG: &str = r#"name = "Test \"song\""
+artist = "Someone"
+genre = "Pop"
+filename = "song.ogg"
+preview_start = 12.5
+preview_end = 27.1
+offset = -0.013
+bpm = 128.0
+
+bpm_changes = [
+    { beat = 64.0, bpm = 64.5 },
+]
+
+time_signatures = [
+    { beat = 0.0, numerator = 3, denominator = 4 },
+]
+
+[[charts]]
+difficulty = "Easy"
+rating = 0
+arrows = [
+    { beat = 1e-7, speed = "Medium", direction = "Right" },
+    { beat = 8.25, speed = "Medium", direction = "Down" },
+]
+
+[[charts]]
+difficulty = "Hard"
+rating = 7
+arrows = [
+    { click_time = 0.1, speed = "Slow", direction = "Left" },
+    { click_time = 1.1, speed = "Fast", direction = "Up", duration = 0.3 },
+]
+"#;
+
+    #[test]
+    fn writes_canonical_form() {
+        assert_eq!(parse(SONG).to_toml(), CANONICAL_SONG);
+    }
+
+    #[test]
+    fn round_trips() {
+        let config = parse(CANONICAL_SONG);
+        assert_eq!(config.to_toml(), CANONICAL_SONG);
+
+        assert_eq!(config.name, "Test \"song\"");
+        assert_eq!(config.metadata.artist.as_deref(), Some("Someone"));
+        assert_eq!(config.metadata.charter, None);
+        assert_eq!(config.metadata.preview_end, Some(27.1));
+        assert_eq!(config.offset, -0.013);
+        assert_eq!(config.bpm_changes[0].bpm, 64.5);
+        assert_eq!(config.time_signatures[0].numerator, 3);
+
+        let easy = config.chart(Difficulty::Easy).unwrap();
+        assert_eq!(easy.arrows[0].beat, Some(1e-7));
+        assert_eq!(easy.arrows[1].beat, Some(8.25));
+        let hard = config.chart(Difficulty::Hard).unwrap();
+        assert_eq!(hard.rating, 7);
+        assert_eq!(hard.arrows[1].click_time, 1.1);
+        assert_eq!(hard.arrows[1].duration, Some(0.3));
+    }
+
+    #[test]
+    fn single_chart_keeps_top_level_arrows() {
+        let contents = "name = \"Song\"\nfilename = \"song.ogg\"\n\narrows = [\n    { click_time = 2.5, speed = \"Slow\", direction = \"Up\" },\n]\n";
+        let config = parse(contents);
+        assert_eq!(config.charts.len(), 1);
+        assert_eq!(config.charts[0].difficulty, Difficulty::Normal);
+        assert_eq!(config.to_toml(), contents);
+    }
+
+    #[test]
+    fn floats_read_back_exactly() {
+        for float in [
+            0.1,
+            1. / 3.,
+            1e-7,
+            123456789.123,
+            2f64.powi(60),
+            f64::MIN_POSITIVE,
+            -2.5,
+        ] {
+            let written = toml_float(float);
+            let read: toml::Value = toml::from_str(&format!("x = {}", written)).unwrap();
+            assert_eq!(read["x"].as_float(), Some(float), "{}", written);
+        }
+        assert_eq!(toml_float(-0.), "0.0");
+        assert_eq!(toml_float(3.), "3.0");
+    }
 }
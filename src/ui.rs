@@ -1,4 +1,10 @@
-use crate::{consts::AppState, ScoreResource};
+use crate::{
+    clock::{SongClock, SongClockLabel},
+    consts::AppState,
+    score::Judgment,
+    types::SongConfig,
+    ScoreResource,
+};
 use bevy::{core::FixedTimestep, prelude::*};
 pub struct UIPlugin;
 impl Plugin for UIPlugin {
@@ -7,14 +13,20 @@ impl Plugin for UIPlugin {
             .add_system_set(
                 SystemSet::on_update(AppState::Game)
                     .with_run_criteria(FixedTimestep::step(0.045))
-                    .with_system(update_time_text.system()),
+                    .with_system(update_time_text.system().after(SongClockLabel)),
             )
             .add_system_set(
-                SystemSet::on_update(AppState::Game).with_system(update_score_text.system()),
-            );
+                SystemSet::on_update(AppState::Game)
+                    .with_system(update_score_text.system())
+                    .with_system(update_judgment_text.system())
+                    .with_system(update_offset_text.system()),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(despawn_ui.system()));
     }
 }
 
+struct GameUI;
+
 fn setup_ui(
     mut commands: Commands,
     asset_server: ResMut<AssetServer>,
@@ -40,6 +52,7 @@ fn setup_ui(
             material: material.clone(),
             ..Default::default()
         })
+        .insert(GameUI)
         .with_children(|parent| {
             parent
                 .spawn_bundle(TextBundle {
@@ -49,7 +62,6 @@ fn setup_ui(
                             font: font.clone(),
                             font_size,
                             color,
-                            ..Default::default()
                         },
                         TextAlignment::default(),
                     ),
@@ -72,16 +84,16 @@ fn setup_ui(
             material,
             ..Default::default()
         })
+        .insert(GameUI)
         .with_children(|parent| {
             parent
                 .spawn_bundle(TextBundle {
                     text: Text::with_section(
-                        ScoreText::get_text(0, 0, 0),
+                        ScoreText::get_text(0, 0, 0, 0, 0),
                         TextStyle {
                             font: font.clone(),
                             font_size,
                             color,
-                            ..Default::default()
                         },
                         TextAlignment::default(),
                     ),
@@ -89,10 +101,80 @@ fn setup_ui(
                 })
                 .insert(ScoreText);
         });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(font_margin),
+                    top: Val::Px(font_margin),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: color_materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(GameUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        JudgmentText::get_text(None, 0, 1., 0),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: font_size * 0.75,
+                            color,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(JudgmentText);
+        });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(font_margin),
+                    bottom: Val::Px(font_margin),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: color_materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .insert(GameUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        OffsetText::get_text(0.),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: font_size * 0.5,
+                            color,
+                        },
+                        TextAlignment::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(OffsetText);
+        });
 }
 
-fn update_time_text(time: Res<Time>, mut query: Query<&mut Text, With<TimeText>>) {
-    let secs = time.seconds_since_startup() - 3.;
+fn despawn_ui(mut commands: Commands, query: Query<Entity, With<GameUI>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_time_text(clock: Res<SongClock>, mut query: Query<&mut Text, With<TimeText>>) {
+    let secs = clock.seconds();
 
     if secs < 0. {
         return;
@@ -108,8 +190,17 @@ fn update_time_text(time: Res<Time>, mut query: Query<&mut Text, With<TimeText>>
 struct ScoreText;
 
 impl ScoreText {
-    pub fn get_text<T: std::fmt::Display>(score: T, corrects: T, fails: T) -> String {
-        format!("Score: {}, Corrects: {}, Fails: {}", score, corrects, fails)
+    pub fn get_text<T: std::fmt::Display>(
+        score: T,
+        corrects: T,
+        fails: T,
+        holds: T,
+        hold_fails: T,
+    ) -> String {
+        format!(
+            "Score: {}, Corrects: {}, Fails: {}\nHolds: {}, Dropped: {}",
+            score, corrects, fails, holds, hold_fails
+        )
     }
 }
 
@@ -117,12 +208,77 @@ fn update_score_text(score: Res<ScoreResource>, mut query: Query<&mut Text, With
     if score.is_changed() {
         for mut text in query.iter_mut() {
             if let Some(ts) = try_get_text_section(&mut text, "Score") {
-                ts.value = ScoreText::get_text(score.score(), score.corrects(), score.fails());
+                ts.value = ScoreText::get_text(
+                    score.score(),
+                    score.corrects(),
+                    score.fails(),
+                    score.holds(),
+                    score.hold_fails(),
+                );
             }
         }
     }
 }
 
+struct JudgmentText;
+
+impl JudgmentText {
+    pub fn get_text(
+        judgment: Option<Judgment>,
+        combo: usize,
+        multiplier: f32,
+        max_combo: usize,
+    ) -> String {
+        format!(
+            "{}\nCombo: {} (x{:.2})\nMax combo: {}",
+            judgment.map_or("", |judgment| judgment.name()),
+            combo,
+            multiplier,
+            max_combo
+        )
+    }
+}
+
+fn update_judgment_text(
+    score: Res<ScoreResource>,
+    mut query: Query<&mut Text, With<JudgmentText>>,
+) {
+    if score.is_changed() {
+        for mut text in query.iter_mut() {
+            text.sections[0].value = JudgmentText::get_text(
+                score.last_judgment(),
+                score.combo(),
+                score.combo_multiplier(),
+                score.max_combo(),
+            );
+        }
+    }
+}
+
+struct OffsetText;
+
+impl OffsetText {
+    /// Empty when the song has no offset, with `-`/`=` to change it otherwise
+    pub fn get_text(offset: f64) -> String {
+        let millis = (offset * 1000.).round();
+        if millis == 0. {
+            String::new()
+        } else {
+            format!("Song offset: {:+} ms (-/=)", millis)
+        }
+    }
+}
+
+fn update_offset_text(song_config: Res<SongConfig>, mut query: Query<&mut Text, With<OffsetText>>) {
+    let value = OffsetText::get_text(song_config.offset);
+    for mut text in query.iter_mut() {
+        // Only setting changed text, so it isn't laid out again every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
 fn try_get_text_section<'a>(text: &'a mut Mut<Text>, pattern: &str) -> Option<&'a mut TextSection> {
     text.sections
         .iter_mut()